@@ -0,0 +1,57 @@
+use rand::Rng;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sycret::fss::dpf::{DPFKey1, DPFKeyAlpha1};
+use sycret::fss::halftree::HalfTreeDPFKey;
+use sycret::utils::Mmo;
+
+pub fn dpf_keygen(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let alpha: u32 = rng.gen();
+
+    let mut group = c.benchmark_group("DPF keygen");
+    group.bench_function("DPFKeyAlpha1", |b| {
+        b.iter(|| DPFKeyAlpha1::<u32>::generate_keypair(&mut prg, black_box(alpha)))
+    });
+    group.bench_function("HalfTreeDPFKey", |b| {
+        b.iter(|| HalfTreeDPFKey::<u32>::generate_keypair(&mut prg, black_box(alpha)))
+    });
+}
+
+pub fn dpf_eval(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let alpha: u32 = rng.gen();
+
+    let (k, _) = DPFKeyAlpha1::<u32>::generate_keypair(&mut prg, alpha);
+    let (h, _) = HalfTreeDPFKey::<u32>::generate_keypair(&mut prg, alpha);
+    let mut group = c.benchmark_group("DPF eval");
+    group.bench_function("DPFKeyAlpha1", |b| {
+        b.iter(|| k.eval(&mut prg, 0, black_box(alpha)))
+    });
+    group.bench_function("HalfTreeDPFKey", |b| {
+        b.iter(|| h.eval(&mut prg, 0, black_box(alpha)))
+    });
+}
+
+pub fn dpf_eval_all(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let alpha: u16 = rng.gen();
+
+    let (k, _) = DPFKeyAlpha1::<u16>::generate_keypair(&mut prg, alpha);
+    let (h, _) = HalfTreeDPFKey::<u16>::generate_keypair(&mut prg, alpha);
+    let mut out = vec![0u32; 1 << 16];
+    let mut group = c.benchmark_group("DPF eval_all 2^16");
+    group.bench_function("DPFKeyAlpha1", |b| b.iter(|| k.eval_all(&prg, 0, &mut out)));
+    group.bench_function("HalfTreeDPFKey", |b| {
+        b.iter(|| h.eval_all(&prg, 0, &mut out))
+    });
+}
+
+criterion_group!(bench_dpf, dpf_keygen, dpf_eval, dpf_eval_all);
+criterion_main!(bench_dpf);
@@ -0,0 +1,40 @@
+use rand::Rng;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sycret::stream::Prg;
+use sycret::utils::Mmo;
+
+pub fn prg_expand(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut mmo = Mmo::new(&aes_keys);
+    let seed: u128 = rng.gen();
+
+    let mut group = c.benchmark_group("Prg expand");
+    group.bench_function("Mmo array", |b| {
+        b.iter(|| mmo.expand_array::<3>(black_box(seed)))
+    });
+}
+
+pub fn prg_batch_expand(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut mmo = Mmo::new(&aes_keys);
+    let seeds: Vec<u128> = (0..1024).map(|_| rng.gen()).collect();
+    let mut out = vec![[0u128; 3]; seeds.len()];
+
+    let mut group = c.benchmark_group("Prg expand 1024 seeds");
+    group.bench_function("Mmo array", |b| {
+        b.iter(|| {
+            for seed in seeds.iter() {
+                black_box(mmo.expand_array::<3>(*seed));
+            }
+        })
+    });
+    group.bench_function("Mmo batch", |b| {
+        b.iter(|| mmo.expand_batch(black_box(&seeds), &mut out))
+    });
+}
+
+criterion_group!(bench_prg, prg_expand, prg_batch_expand);
+criterion_main!(bench_prg);
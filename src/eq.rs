@@ -2,80 +2,133 @@
 //! Equality keys tailored for AriaNN
 //!
 
-use rand::Rng;
+use rand::{CryptoRng, Rng, RngCore};
 use std::convert::TryInto;
 
 use std::slice;
 
-use crate::fss::dpf::{g, generate_cw_from_seeds};
+use crate::fss::dpf::{eval_path, eval_paths, generate_cw_from_seeds};
 use crate::stream::{FSSKey, Prg, RawKey};
-use crate::utils::{bit_decomposition_u32, compute_out};
-use crate::{L, N};
-
+use crate::utils::{
+    compute_out, random_domain_element, read_domain_element, wrapping_sub_domain,
+    write_domain_element, Domain, Group,
+};
+use crate::L;
+
+/// Unused bytes before the leaf of the 32 bits keys.
+/// They preserve the v0.2 raw line layout (621 bytes), which the FFI still reads.
+const LEGACY_U32_PADDING: usize = 21;
+
+/// Equality key for inputs in T, with one tree level per bit of T.
+/// The output is shared in G.
 #[derive(Debug)]
-pub struct EqKey {
-    pub alpha_share: u32,
+pub struct GenericEqKey<T: Domain, G: Group = u32> {
+    pub alpha_share: T,
     pub s: u128,
-    pub cw: [u128; N * 8],
-    pub t_l: [u8; N * 8],
-    pub t_r: [u8; N * 8],
-    pub cw_leaf: u32,
+    pub cw: Vec<u128>,
+    pub t_l: Vec<u8>,
+    pub t_r: Vec<u8>,
+    pub cw_leaf: G,
 }
 
-impl RawKey for EqKey {
-    const KEY_LEN: usize = 621;
+/// Equality key on 32 bits, used by the FFI.
+pub type EqKey = GenericEqKey<u32>;
+
+/// Equality key on 32 bits with shares in Z/2^64Z, used by the FFI.
+pub type EqKey64 = GenericEqKey<u32, u64>;
+
+/// Equality key on 32 bits with XOR shares, used by the FFI.
+pub type EqKeyXor = GenericEqKey<u32, bool>;
+
+impl<T: Domain, G: Group> RawKey for GenericEqKey<T, G> {
+    // N + 16 + 18 * (N * 8) + G, with the legacy padding before the leaf on 32 bits.
+    const KEY_LEN: usize = T::N
+        + L
+        + T::N * 8 * (L + 2)
+        + G::BYTES
+        + if T::N == 4 && G::BYTES == 4 {
+            LEGACY_U32_PADDING
+        } else {
+            0
+        };
 
     unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
-        // Cast the output line to a raw pointer.
-        let out_ptr: *mut [u8; Self::KEY_LEN] =
-            slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN).as_mut_ptr()
-                as *mut [u8; Self::KEY_LEN];
-        // Get a mutable reference.
-        let out_ref: &mut [u8; Self::KEY_LEN] = &mut *out_ptr;
+        // Get a mutable reference to the output line.
+        let out_ref: &mut [u8] = slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN);
         // Write the key.
         write_key_to_array(self, out_ref);
     }
 
     unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
-        let key_ptr = slice::from_raw_parts(key_pointer, Self::KEY_LEN).as_ptr()
-            as *const [u8; Self::KEY_LEN];
-        read_key_from_array(&*key_ptr)
+        read_key_from_array(slice::from_raw_parts(key_pointer, Self::KEY_LEN))
     }
 }
 
-impl FSSKey for EqKey {
-    fn generate_keypair(prg: &mut impl Prg) -> (Self, Self) {
-        // Thread randomness for parallelization.
-        let mut rng = rand::thread_rng();
+impl<T: Domain, G: Group> FSSKey for GenericEqKey<T, G> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        // Random point on which we will check equality, secret-shared with a random mask.
+        let alpha: T = random_domain_element(rng);
+        let mask: T = random_domain_element(rng);
+        Self::generate_keypair_from_alpha(prg, alpha, mask, rng)
+    }
 
-        // Random point on which we will check equality.
-        let alpha: u32 = rng.gen();
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        let (s_n, t_n) = eval_path(prg, party_id, self.s, &self.cw, &self.t_l, &self.t_r, x);
+        compute_out(G::from_block(s_n), self.cw_leaf, t_n, party_id)
+    }
 
+    fn eval_batch(keys: &[Self], prg: &mut impl Prg, party_id: u8, xs: &[T], out: &mut [G]) {
+        let trees: Vec<_> = keys
+            .iter()
+            .map(|key| (key.s, &key.cw[..], &key.t_l[..], &key.t_r[..]))
+            .collect();
+        let mut leaves = vec![(0u128, 0u8); keys.len()];
+        eval_paths(prg, party_id, &trees, xs, &mut leaves);
+        for ((key, (s_n, t_n)), out) in keys.iter().zip(leaves).zip(out.iter_mut()) {
+            *out = compute_out(G::from_block(s_n), key.cw_leaf, t_n, party_id);
+        }
+    }
+}
+
+impl<T: Domain, G: Group> GenericEqKey<T, G> {
+    /// Keys for a given alpha, e.g. a mask preprocessed by another protocol.
+    /// Bob's share of alpha is `mask` and Alice's share is alpha - mask.
+    /// Only the seeds are drawn from `rng`.
+    pub fn generate_keypair_from_alpha(
+        prg: &mut impl Prg,
+        alpha: T,
+        mask: T,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
         // Initialize seeds.
         let s_a: u128 = rng.gen();
         let s_b: u128 = rng.gen();
 
         // Memory allocation. We could write inplace instead.
-        let mut cw = [0u128; N * 8];
-        let mut t_l = [0u8; N * 8];
-        let mut t_r = [0u8; N * 8];
-
-        let cw_leaf = generate_cw_from_seeds(prg, alpha, s_a, s_b, &mut cw, &mut t_l, &mut t_r);
+        let mut cw = vec![0u128; T::N * 8];
+        let mut t_l = vec![0u8; T::N * 8];
+        let mut t_r = vec![0u8; T::N * 8];
 
-        // Secret-share alpha and split the keys between Alice and Bob.
-        let mask: u32 = rng.gen();
+        let cw_leaf =
+            generate_cw_from_seeds(prg, alpha, G::one(), s_a, s_b, &mut cw, &mut t_l, &mut t_r);
 
-        // Return a key pair.
+        // Return a key pair, with alpha secret-shared between Alice and Bob.
         (
-            EqKey {
-                alpha_share: alpha.wrapping_sub(mask),
+            GenericEqKey {
+                alpha_share: wrapping_sub_domain(alpha, mask),
                 s: s_a,
-                cw,
-                t_l,
-                t_r,
+                cw: cw.clone(),
+                t_l: t_l.clone(),
+                t_r: t_r.clone(),
                 cw_leaf,
             },
-            EqKey {
+            GenericEqKey {
                 alpha_share: mask,
                 s: s_b,
                 cw,
@@ -85,87 +138,50 @@ impl FSSKey for EqKey {
             },
         )
     }
-
-    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32 {
-        // Initialize the control bit and the seed.
-        assert!((party_id == 0u8) || (party_id == 1u8));
-        let mut t_i: u8 = party_id;
-        let mut s_i: u128 = self.s;
-
-        // Compare the bit decomposition of x with the special path.
-        let x_bits: Vec<u8> = bit_decomposition_u32(x);
-        for i in 0..(N * 8) {
-            let (s_l, t_l, s_r, t_r) = g(prg, s_i);
-            let s_cw = self.cw[i];
-            let t_cw_l = self.t_l[i];
-            let t_cw_r = self.t_r[i];
-
-            // We don't compute the XOR on the side that we don't keep.
-            if x_bits[i] == 0u8 {
-                // If x[i] = 0, keep left.
-                if t_i == 0u8 {
-                    s_i = s_l;
-                    t_i = t_l;
-                } else {
-                    s_i = s_l ^ s_cw;
-                    t_i = t_l ^ t_cw_l;
-                }
-            } else {
-                // If x[i] = 1, keep right.
-                if t_i == 0u8 {
-                    s_i = s_r;
-                    t_i = t_r;
-                } else {
-                    s_i = s_r ^ s_cw;
-                    t_i = t_r ^ t_cw_r;
-                }
-            }
-        }
-        compute_out(s_i as u32, self.cw_leaf, t_i, party_id)
-    }
 }
 
-///
-/// Serialization
-///
+//
+// Serialization
+//
 
-fn write_key_to_array(key: &EqKey, array: &mut [u8; EqKey::KEY_LEN]) {
-    array[0..N].copy_from_slice(&key.alpha_share.to_le_bytes());
-    array[N..(N + L)].copy_from_slice(&key.s.to_le_bytes());
-    for i in 0..(N * 8) {
+fn write_key_to_array<T: Domain, G: Group>(key: &GenericEqKey<T, G>, array: &mut [u8]) {
+    let n = T::N;
+    write_domain_element(key.alpha_share, &mut array[0..n]);
+    array[n..(n + L)].copy_from_slice(&key.s.to_le_bytes());
+    for i in 0..(n * 8) {
         // This key structure is retrocompatible with byte arrays.
-        let cw_start = N + L + i * (L + 2);
-        let cw_end = N + L + (i + 1) * (L + 2);
+        let cw_start = n + L + i * (L + 2);
+        let cw_end = n + L + (i + 1) * (L + 2);
         array[cw_start..cw_end - 2].copy_from_slice(&key.cw[i].to_le_bytes());
         array[cw_end - 2] = key.t_l[i];
         array[cw_end - 1] = key.t_r[i];
     }
-    // array[EqKey::KEY_LEN - 1] = key.t_leaf;
-    let j = EqKey::KEY_LEN - N;
-    array[j..j + N].copy_from_slice(&key.cw_leaf.to_le_bytes());
+    // The leaf is always at the end of the line.
+    let j = GenericEqKey::<T, G>::KEY_LEN - G::BYTES;
+    key.cw_leaf.write_le_bytes(&mut array[j..j + G::BYTES]);
 }
 
-fn read_key_from_array(array: &[u8; EqKey::KEY_LEN]) -> EqKey {
-    let alpha_share = u32::from_le_bytes(array[0..N].try_into().unwrap());
-    let s = u128::from_le_bytes(array[N..(N + L)].try_into().unwrap());
+fn read_key_from_array<T: Domain, G: Group>(array: &[u8]) -> GenericEqKey<T, G> {
+    let n = T::N;
+    let alpha_share = read_domain_element(&array[0..n]);
+    let s = u128::from_le_bytes(array[n..(n + L)].try_into().unwrap());
 
-    let mut cw = [0u128; N * 8];
-    let mut t_l = [0u8; N * 8];
-    let mut t_r = [0u8; N * 8];
+    let mut cw = vec![0u128; n * 8];
+    let mut t_l = vec![0u8; n * 8];
+    let mut t_r = vec![0u8; n * 8];
 
-    for i in 0..(N * 8) {
-        let cw_start = N + L + i * (L + 2);
-        let cw_end = N + L + (i + 1) * (L + 2);
+    for i in 0..(n * 8) {
+        let cw_start = n + L + i * (L + 2);
+        let cw_end = n + L + (i + 1) * (L + 2);
         cw[i] = u128::from_le_bytes(array[cw_start..cw_end - 2].try_into().unwrap());
         t_l[i] = array[cw_end - 2];
         t_r[i] = array[cw_end - 1];
     }
 
-    // let t_leaf = array[EqKey::KEY_LEN - 1];
-    let j = EqKey::KEY_LEN - N;
-    let cw_leaf = u32::from_le_bytes(array[j..j + N].try_into().unwrap());
+    let j = GenericEqKey::<T, G>::KEY_LEN - G::BYTES;
+    let cw_leaf = G::read_le_bytes(&array[j..j + G::BYTES]);
 
-    EqKey {
+    GenericEqKey {
         alpha_share,
         s,
         cw,
@@ -0,0 +1,11 @@
+//!
+//! DCF implementation, i.e. beta * 1[x < alpha] on top of the DIF trees
+//!
+
+use crate::fss::dif::{DIFKey, Lt};
+use crate::fss::Beta;
+
+/// Distributed comparison function key for alpha in T and beta in G given at Keygen time.
+/// The output shares beta on every x < alpha and 0 elsewhere.
+/// Any payload group of at most 8 bytes fits in the tree, e.g. `Pair<u32>` for a vector payload.
+pub type DCFKey<T = u32, G = u32> = DIFKey<T, G, Lt, Beta>;
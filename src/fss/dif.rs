@@ -2,124 +2,531 @@
 //! DIF implementation
 //!
 
+use std::convert::TryInto;
+use std::marker::PhantomData;
+
+use crate::fss::{eval_range_depth_first, Beta, One};
 use crate::stream::Prg;
-use crate::utils::{bit_decomposition_u32, compute_out, share_leaf};
-use crate::{L, N};
-use rand::Rng;
+use crate::utils::{bit_decomposition, compute_out, share_leaf, Domain, Group};
+use crate::L;
+use rand::{CryptoRng, Rng, RngCore};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub struct CorrectionWord {
-    pub z_l: u32,
+pub struct CorrectionWord<G: Group = u32> {
+    pub z_l: G,
     pub u_l: u8,
     pub s_l: u128,
     pub t_l: u8,
-    pub z_r: u32,
+    pub z_r: G,
     pub u_r: u8,
     pub s_r: u128,
     pub t_r: u8,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub struct CompressedCorrectionWord {
+pub struct CompressedCorrectionWord<G: Group = u32> {
     pub u_l: u8,
     pub t_l: u8,
     pub u_r: u8,
     pub t_r: u8,
-    pub z: u32,
+    pub z: G,
     pub s: u128,
 }
 
-/// DIF Key for alpha in u32 given at Keygen time and beta = 1
+/// DIF Key for alpha in T, comparing the input with alpha as given by C.
+/// There is one correction word per bit of T, and one more leaf for the last level.
+/// The output is shared in G, e.g. Z/2^32Z for u32 or Z/2^64Z for u64.
+/// Only the leaf correction words depend on C and on the payload B.
 #[derive(Debug)]
-pub struct DIFKeyAlpha1 {
+pub struct DIFKey<T: Domain = u32, G: Group = u32, C = Le, B = One> {
     pub s: u128,
-    pub cw: [CompressedCorrectionWord; N * 8],
-    pub cw_leaf: [u32; N * 8 + 1],
+    pub cw: Vec<CompressedCorrectionWord<G>>,
+    pub cw_leaf: Vec<G>,
+    domain: PhantomData<(T, C, B)>,
+}
+
+/// DIF Key for alpha in T given at Keygen time and beta = 1.
+pub type DIFKeyAlpha1<T = u32, G = u32> = DIFKey<T, G, Le, One>;
+
+/// DIF Key for alpha in T and beta in G given at Keygen time.
+pub type DIFKeyAlphaBeta<T = u32, G = u32> = DIFKey<T, G, Le, Beta>;
+
+/// DIF Key for x < alpha, with alpha given at Keygen time and beta = 1.
+pub type DIFKeyLt<T = u32, G = u32> = DIFKey<T, G, Lt, One>;
+
+/// DIF Key for x > alpha, with alpha given at Keygen time and beta = 1.
+pub type DIFKeyGt<T = u32, G = u32> = DIFKey<T, G, Gt, One>;
+
+/// DIF Key for x >= alpha, with alpha given at Keygen time and beta = 1.
+pub type DIFKeyGe<T = u32, G = u32> = DIFKey<T, G, Ge, One>;
+
+/// Comparison of the input with alpha, given by the side of alpha the leaves share beta on.
+pub trait Comparison {
+    /// The values shared on x < alpha, x = alpha and x > alpha.
+    fn leaves<G: Group>(beta: G) -> (G, G, G);
 }
 
+// The leaves share beta on the side of alpha given by (below, equal, above).
+macro_rules! impl_dif_comparison {
+    ($(#[$doc:meta])* $marker:ident, $below:expr, $equal:expr, $above:expr) => {
+        $(#[$doc])*
+        #[derive(Debug)]
+        pub struct $marker;
+
+        impl Comparison for $marker {
+            fn leaves<G: Group>(beta: G) -> (G, G, G) {
+                let side = |on: bool| if on { beta } else { G::zero() };
+                (side($below), side($equal), side($above))
+            }
+        }
+    };
+}
+
+impl_dif_comparison!(
+    /// x <= alpha.
+    Le, true, true, false
+);
+impl_dif_comparison!(
+    /// x < alpha.
+    Lt, true, false, false
+);
+impl_dif_comparison!(
+    /// x > alpha.
+    Gt, false, false, true
+);
+impl_dif_comparison!(
+    /// x >= alpha.
+    Ge, false, true, true
+);
+
 pub trait DIFKey1: Sized {
-    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32;
+    type Input: Domain;
+    type Output: Group;
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: Self::Input) -> Self::Output;
 
-    fn generate_keypair(prg: &mut impl Prg, alpha: u32) -> (Self, Self);
+    /// Keys with the seeds drawn from `rng`.
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self);
+
+    fn generate_keypair(prg: &mut impl Prg, alpha: Self::Input) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, alpha, &mut rand::thread_rng())
+    }
+}
+
+pub trait DIFKeyBeta: Sized {
+    type Input: Domain;
+    type Output: Group;
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: Self::Input) -> Self::Output;
+
+    /// Keys with the seeds drawn from `rng`.
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        beta: Self::Output,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self);
+
+    fn generate_keypair(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        beta: Self::Output,
+    ) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, alpha, beta, &mut rand::thread_rng())
+    }
+}
+
+impl<T: Domain, G: Group, C: Comparison> DIFKey1 for DIFKey<T, G, C, One> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: T,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        Self::keypair_with_beta(prg, alpha, G::one(), rng)
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        // The output is a share of the comparison of x with alpha in G
+        eval_path(prg, party_id, self.s, &self.cw, &self.cw_leaf, x)
+    }
 }
 
-impl DIFKey1 for DIFKeyAlpha1 {
-    fn generate_keypair(prg: &mut impl Prg, alpha: u32) -> (Self, Self) {
-        // Thread randomness for parallelization.
-        let mut rng = rand::thread_rng();
+impl<T: Domain, G: Group, C: Comparison> DIFKeyBeta for DIFKey<T, G, C, Beta> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: T,
+        beta: G,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        Self::keypair_with_beta(prg, alpha, beta, rng)
+    }
 
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        // The output is a share of beta times the comparison of x with alpha in G
+        eval_path(prg, party_id, self.s, &self.cw, &self.cw_leaf, x)
+    }
+}
+
+impl<T: Domain, G: Group, C: Comparison, B> DIFKey<T, G, C, B> {
+    // Keys sharing beta on the side of alpha given by C.
+    fn keypair_with_beta(
+        prg: &mut impl Prg,
+        alpha: T,
+        beta: G,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
         // Initialize seeds.
         let s_a: u128 = rng.gen();
         let s_b: u128 = rng.gen();
 
-        let (cw, cw_leaf) = generate_cw_from_seeds(prg, alpha, s_a, s_b);
+        let (below, equal, above) = C::leaves(beta);
+        let (cw, cw_leaf) = generate_cw_from_seeds_with(prg, alpha, below, equal, above, s_a, s_b);
 
         // Return a key pair.
         (
-            DIFKeyAlpha1 {
+            DIFKey {
                 s: s_a,
-                cw,
-                cw_leaf,
+                cw: cw.clone(),
+                cw_leaf: cw_leaf.clone(),
+                domain: PhantomData,
             },
-            DIFKeyAlpha1 {
+            DIFKey {
                 s: s_b,
                 cw,
                 cw_leaf,
+                domain: PhantomData,
             },
         )
     }
 
-    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32 {
-        assert!((party_id == 0u8) || (party_id == 1u8));
-        let mut t_i: u8 = party_id;
-        let mut s_i: u128 = self.s;
-        let mut u_i;
-        let mut z_i;
-        let mut out = 0u32;
-        let x_bits: Vec<u8> = bit_decomposition_u32(x);
-        for i in 0..(N * 8) {
-            let mut w = h(prg, s_i);
-            if t_i == 1 {
-                w = xor_2_words(&w, &decompress_word(&self.cw[i]))
+    /// Shares of the whole domain, `out` must have 2^(N * 8) elements.
+    pub fn eval_all<P: Prg + Clone + Sync>(&self, prg: &P, party_id: u8, out: &mut [G]) {
+        assert_eq!(out.len() as u128, 1u128 << (T::N * 8));
+        self.eval_range(prg, party_id, T::from_u64(0), out);
+    }
+
+    /// Shares on x = start, start + 1, ..., start + out.len() - 1, without wrapping around.
+    pub fn eval_range<P: Prg + Clone + Sync>(
+        &self,
+        prg: &P,
+        party_id: u8,
+        start: T,
+        out: &mut [G],
+    ) {
+        eval_range_into(prg, party_id, self.s, &self.cw, &self.cw_leaf, start, out);
+    }
+
+    /// Shares on each of the points, with one walk down the tree for all of them.
+    pub fn eval_points(&self, prg: &mut impl Prg, party_id: u8, xs: &[T]) -> Vec<G> {
+        eval_points(prg, party_id, self.s, &self.cw, &self.cw_leaf, xs)
+    }
+}
+
+///
+/// Walks down the tree along the bits of x and sums the masked outputs of each level.
+/// The tree has one level per correction word, and only the lowest bits of x are read
+/// when it is smaller than the domain of T.
+///
+pub fn eval_path<T: Domain, G: Group>(
+    prg: &mut impl Prg,
+    party_id: u8,
+    s: u128,
+    cw: &[CompressedCorrectionWord<G>],
+    cw_leaf: &[G],
+    x: T,
+) -> G {
+    assert!((party_id == 0u8) || (party_id == 1u8));
+    let mut t_i: u8 = party_id;
+    let mut s_i: u128 = s;
+    let mut u_i;
+    let mut z_i;
+    let mut out = G::zero();
+    let n_bits = cw.len();
+    let x_bits: Vec<u8> = bit_decomposition(x);
+    let x_bits = &x_bits[x_bits.len() - n_bits..];
+    for i in 0..n_bits {
+        let mut w = h(prg, s_i);
+        if t_i == 1 {
+            w = xor_2_words(&w, &decompress_word(&cw[i]))
+        }
+        if x_bits[i] == 0 {
+            z_i = w.z_l;
+            u_i = w.u_l;
+            s_i = w.s_l;
+            t_i = w.t_l;
+        } else {
+            z_i = w.z_r;
+            u_i = w.u_r;
+            s_i = w.s_r;
+            t_i = w.t_r;
+        }
+
+        // Mask and sum in G
+        let out_i = compute_out(z_i, cw_leaf[i], u_i, party_id);
+        out = out.add(out_i);
+    }
+    let out_n = compute_out(G::from_block(s_i), cw_leaf[n_bits], t_i, party_id);
+    out.add(out_n)
+}
+
+/// Seed, correction words and leaf correction words of a DIF tree, e.g. for `eval_paths`.
+pub type DIFTree<'a, G> = (u128, &'a [CompressedCorrectionWord<G>], &'a [G]);
+
+///
+/// Same as `eval_path` on xs[j] with the j-th tree, for trees of the same depth.
+/// The paths are walked in lockstep, so each level is a single call to `Prg::expand_batch`.
+///
+pub fn eval_paths<T: Domain, G: Group>(
+    prg: &mut impl Prg,
+    party_id: u8,
+    trees: &[DIFTree<G>],
+    xs: &[T],
+    out: &mut [G],
+) {
+    assert!((party_id == 0u8) || (party_id == 1u8));
+    assert_eq!(trees.len(), xs.len());
+    assert_eq!(trees.len(), out.len());
+    let n_bits = trees.first().map_or(0, |tree| tree.1.len());
+    assert!(trees.iter().all(|tree| tree.1.len() == n_bits));
+
+    let mut s: Vec<u128> = trees.iter().map(|tree| tree.0).collect();
+    let mut t: Vec<u8> = vec![party_id; trees.len()];
+    let mut blocks = vec![[0u128; 3]; trees.len()];
+    for out_j in out.iter_mut() {
+        *out_j = G::zero();
+    }
+    for i in 0..n_bits {
+        prg.expand_batch(&s, &mut blocks);
+        for (j, (_, cw, cw_leaf)) in trees.iter().enumerate() {
+            let mut w = h_from_blocks(blocks[j]);
+            if t[j] == 1 {
+                w = xor_2_words(&w, &decompress_word(&cw[i]))
             }
-            if x_bits[i] == 0 {
-                z_i = w.z_l;
-                u_i = w.u_l;
-                s_i = w.s_l;
-                t_i = w.t_l;
-            } else {
-                z_i = w.z_r;
-                u_i = w.u_r;
-                s_i = w.s_r;
-                t_i = w.t_r;
+            let (z_i, u_i) = match (xs[j].to_u64() >> (n_bits - 1 - i)) & 1 {
+                0 => {
+                    s[j] = w.s_l;
+                    t[j] = w.t_l;
+                    (w.z_l, w.u_l)
+                }
+                _ => {
+                    s[j] = w.s_r;
+                    t[j] = w.t_r;
+                    (w.z_r, w.u_r)
+                }
+            };
+
+            // Mask and sum in G
+            out[j] = out[j].add(compute_out(z_i, cw_leaf[i], u_i, party_id));
+        }
+    }
+    for (j, (_, _, cw_leaf)) in trees.iter().enumerate() {
+        let out_n = compute_out(G::from_block(s[j]), cw_leaf[n_bits], t[j], party_id);
+        out[j] = out[j].add(out_n);
+    }
+}
+
+///
+/// Evaluates the tree on every x in [start, start + out.len()) and writes the shares in out.
+/// The sum of the masked outputs is carried down the tree, so each node is expanded once.
+/// The tree must have one level per bit of T.
+///
+pub fn eval_range_into<T: Domain, G: Group, P: Prg + Clone + Sync>(
+    prg: &P,
+    party_id: u8,
+    s: u128,
+    cw: &[CompressedCorrectionWord<G>],
+    cw_leaf: &[G],
+    start: T,
+    out: &mut [G],
+) {
+    assert!((party_id == 0u8) || (party_id == 1u8));
+    let n_bits = T::N * 8;
+    assert_eq!(
+        cw.len(),
+        n_bits,
+        "The tree must have one level per bit of the domain"
+    );
+    assert_eq!(cw_leaf.len(), n_bits + 1);
+
+    // Nodes are (seed, control bit, sum of the outputs of the previous levels).
+    let seed = |node: &(u128, u8, G)| node.0;
+    let expand = |_: &mut P, blocks: [u128; 3], node: &(u128, u8, G), level: usize| {
+        let (_, t_i, out_i) = *node;
+        let mut w = h_from_blocks(blocks);
+        if t_i == 1 {
+            w = xor_2_words(&w, &decompress_word(&cw[level]))
+        }
+        let out_l = out_i.add(compute_out(w.z_l, cw_leaf[level], w.u_l, party_id));
+        let out_r = out_i.add(compute_out(w.z_r, cw_leaf[level], w.u_r, party_id));
+        ((w.s_l, w.t_l, out_l), (w.s_r, w.t_r, out_r))
+    };
+    let leaf = |node: &(u128, u8, G)| {
+        let (s_n, t_n, out_n) = *node;
+        out_n.add(compute_out(
+            G::from_block(s_n),
+            cw_leaf[n_bits],
+            t_n,
+            party_id,
+        ))
+    };
+
+    eval_range_depth_first(
+        prg,
+        (s, party_id, G::zero()),
+        n_bits,
+        start.to_u64(),
+        out,
+        seed,
+        expand,
+        leaf,
+    );
+}
+
+///
+/// Evaluates the tree on every x in xs with a single walk: the points are sorted,
+/// and each node on the union of their paths is expanded once.
+///
+pub fn eval_points<T: Domain, G: Group>(
+    prg: &mut impl Prg,
+    party_id: u8,
+    s: u128,
+    cw: &[CompressedCorrectionWord<G>],
+    cw_leaf: &[G],
+    xs: &[T],
+) -> Vec<G> {
+    assert!((party_id == 0u8) || (party_id == 1u8));
+    let mut order: Vec<usize> = (0..xs.len()).collect();
+    order.sort_by_key(|&i| xs[i].to_u64());
+
+    let mut out = vec![G::zero(); xs.len()];
+    let walk = PointsWalk {
+        party_id,
+        cw,
+        cw_leaf,
+        xs,
+    };
+    walk.visit(prg, &order, 0, (s, party_id, G::zero()), &mut out);
+    out
+}
+
+// Shared state of the walk in `eval_points`.
+struct PointsWalk<'a, T: Domain, G: Group> {
+    party_id: u8,
+    cw: &'a [CompressedCorrectionWord<G>],
+    cw_leaf: &'a [G],
+    xs: &'a [T],
+}
+
+impl<'a, T: Domain, G: Group> PointsWalk<'a, T, G> {
+    // Visits the node (seed, control bit, sum of the outputs of the previous levels)
+    // shared by the sorted points in `order`.
+    fn visit(
+        &self,
+        prg: &mut impl Prg,
+        order: &[usize],
+        level: usize,
+        node: (u128, u8, G),
+        out: &mut [G],
+    ) {
+        let n_bits = T::N * 8;
+        let (s_i, t_i, out_i) = node;
+        if level == n_bits {
+            let out_n = out_i.add(compute_out(
+                G::from_block(s_i),
+                self.cw_leaf[n_bits],
+                t_i,
+                self.party_id,
+            ));
+            for &i in order {
+                out[i] = out_n;
             }
+            return;
+        }
 
-            // Mask and sum in Z/2^32Z
-            let out_i = compute_out(z_i, self.cw_leaf[i], u_i, party_id);
-            out = out.wrapping_add(out_i);
+        let mut w = h(prg, s_i);
+        if t_i == 1 {
+            w = xor_2_words(&w, &decompress_word(&self.cw[level]))
+        }
+
+        // Points are sorted and share the prefix of this node, so they are split by one bit.
+        let shift = n_bits - 1 - level;
+        let split = order.partition_point(|&i| (self.xs[i].to_u64() >> shift) & 1 == 0);
+        let (left, right) = order.split_at(split);
+        if !left.is_empty() {
+            let out_l = out_i.add(compute_out(
+                w.z_l,
+                self.cw_leaf[level],
+                w.u_l,
+                self.party_id,
+            ));
+            self.visit(prg, left, level + 1, (w.s_l, w.t_l, out_l), out);
+        }
+        if !right.is_empty() {
+            let out_r = out_i.add(compute_out(
+                w.z_r,
+                self.cw_leaf[level],
+                w.u_r,
+                self.party_id,
+            ));
+            self.visit(prg, right, level + 1, (w.s_r, w.t_r, out_r), out);
         }
-        let out_n = compute_out(s_i as u32, self.cw_leaf[N * 8], t_i, party_id);
-        // The final sum is a share of (x <= alpha) in Z/2^32Z
-        out.wrapping_add(out_n)
     }
 }
 
-pub fn h(prg: &mut impl Prg, seed: u128) -> CorrectionWord {
-    assert_eq!(L, 128 / 8);
+///
+/// Wrapper around the first 3 blocks of the Prg, i.e. `P::EXPANSION >= 3`
+///
+pub fn h<G: Group>(prg: &mut impl Prg, seed: u128) -> CorrectionWord<G> {
+    h_from_blocks(prg.expand_array(seed))
+}
 
-    let out = prg.expand(seed);
+/// Same as `h`, from blocks already expanded, e.g. by `Prg::expand_batch`.
+pub fn h_from_blocks<G: Group>(out: [u128; 3]) -> CorrectionWord<G> {
+    assert_eq!(L, 128 / 8);
+    // Both z values are cut from the third block, so G must fit in 64 bits.
+    const { assert!(G::BYTES <= 8, "G does not fit in the DIF tree") };
 
     // Get the randomness and chop the control bits.
-    let s_l = out[0];
-    let s_r = out[1];
-    let z_l = out[2] as u32;
-    let z_r = (out[2] >> 32) as u32;
-
-    // TODO: A 3x expansion PRG is slightly overkill
-    let t_l = (out[2] >> 64) as u8 & 1u8;
-    let u_l = (out[2] >> 65) as u8 & 1u8;
-    let t_r = (out[2] >> 66) as u8 & 1u8;
-    let u_r = (out[2] >> 67) as u8 & 1u8;
+    let mut s_l = out[0];
+    let mut s_r = out[1];
+    let z_l;
+    let z_r;
+    let t_l;
+    let u_l;
+    let t_r;
+    let u_r;
+
+    if G::BYTES <= 4 {
+        z_l = G::from_block(out[2]);
+        z_r = G::from_block(out[2] >> 32);
+
+        // TODO: A 3x expansion PRG is slightly overkill
+        t_l = (out[2] >> 64) as u8 & 1u8;
+        u_l = (out[2] >> 65) as u8 & 1u8;
+        t_r = (out[2] >> 66) as u8 & 1u8;
+        u_r = (out[2] >> 67) as u8 & 1u8;
+    } else {
+        // The z values take the whole third block, chop the control bits from the seeds.
+        z_l = G::from_block(out[2]);
+        z_r = G::from_block(out[2] >> 64);
+
+        t_l = s_l as u8 & 1u8;
+        u_l = (s_l >> 1) as u8 & 1u8;
+        t_r = s_r as u8 & 1u8;
+        u_r = (s_r >> 1) as u8 & 1u8;
+        s_l = s_l >> 2 << 2;
+        s_r = s_r >> 2 << 2;
+    }
 
     CorrectionWord {
         s_l,
@@ -134,22 +541,67 @@ pub fn h(prg: &mut impl Prg, seed: u128) -> CorrectionWord {
 }
 
 /// Internal deterministic function.
-pub fn generate_cw_from_seeds(
+/// Returns one correction word per bit of alpha, and one more leaf than correction words.
+/// The leaves share beta on every x <= alpha.
+pub fn generate_cw_from_seeds<T: Domain, G: Group>(
     prg: &mut impl Prg,
-    alpha: u32,
+    alpha: T,
+    beta: G,
     s_a: u128,
     s_b: u128,
-) -> ([CompressedCorrectionWord; N * 8], [u32; N * 8 + 1]) {
-    // Initialize the output control words. Arrays instead of vectors for CFFI.
-    let mut cw = [CompressedCorrectionWord {
-        s: 0,
-        z: 0,
-        t_l: 0,
-        t_r: 0,
-        u_l: 0,
-        u_r: 0,
-    }; N * 8];
-    let mut cw_leaf = [0u32; N * 8 + 1];
+) -> (Vec<CompressedCorrectionWord<G>>, Vec<G>) {
+    generate_cw_from_seeds_with(prg, alpha, beta, beta, G::zero(), s_a, s_b)
+}
+
+/// Internal deterministic function, for any comparison with alpha.
+/// The leaves share `below` on every x < alpha, `equal` on alpha and `above` on every x > alpha.
+pub fn generate_cw_from_seeds_with<T: Domain, G: Group>(
+    prg: &mut impl Prg,
+    alpha: T,
+    below: G,
+    equal: G,
+    above: G,
+    s_a: u128,
+    s_b: u128,
+) -> (Vec<CompressedCorrectionWord<G>>, Vec<G>) {
+    generate_cw_from_bits(
+        prg,
+        &bit_decomposition(alpha),
+        below,
+        equal,
+        above,
+        s_a,
+        s_b,
+    )
+}
+
+/// Same as `generate_cw_from_seeds_with`, for a tree with one level per bit of `alpha_bits`
+/// (most significant bits first), e.g. a tree on the lowest bits of the domain.
+pub fn generate_cw_from_bits<G: Group>(
+    prg: &mut impl Prg,
+    alpha_bits: &[u8],
+    below: G,
+    equal: G,
+    above: G,
+    s_a: u128,
+    s_b: u128,
+) -> (Vec<CompressedCorrectionWord<G>>, Vec<G>) {
+    const { assert!(G::BYTES <= 8, "G does not fit in the DIF tree") };
+    let n_bits = alpha_bits.len();
+
+    // Initialize the output control words.
+    let mut cw = vec![
+        CompressedCorrectionWord {
+            s: 0,
+            z: G::zero(),
+            t_l: 0,
+            t_r: 0,
+            u_l: 0,
+            u_r: 0,
+        };
+        n_bits
+    ];
+    let mut cw_leaf = vec![G::zero(); n_bits + 1];
 
     // Initialize control bits.
     let mut t_a_i = 0u8;
@@ -164,11 +616,12 @@ pub fn generate_cw_from_seeds(
     let mut z_b_i;
 
     // Iterate over the bits of alpha
-    let alpha_bits = bit_decomposition_u32(alpha);
-
-    for i in 0..(N * 8) {
-        let w_a = h(prg, s_a_i);
-        let w_b = h(prg, s_b_i);
+    let mut blocks = [[0u128; 3]; 2];
+    for i in 0..n_bits {
+        // Both paths are expanded at once.
+        prg.expand_batch(&[s_a_i, s_b_i], &mut blocks);
+        let w_a: CorrectionWord<G> = h_from_blocks(blocks[0]);
+        let w_b: CorrectionWord<G> = h_from_blocks(blocks[1]);
 
         let mut cw_i = match alpha_bits[i] {
             1 => CorrectionWord {
@@ -176,13 +629,13 @@ pub fn generate_cw_from_seeds(
                 u_l: 1,
                 s_l: 0,
                 t_l: 0,
-                z_r: 0,
+                z_r: G::zero(),
                 u_r: 0,
                 s_r: w_a.s_l ^ w_b.s_l,
                 t_r: 1,
             },
             _ => CorrectionWord {
-                z_l: 0,
+                z_l: G::zero(),
                 u_l: 0,
                 s_l: w_a.s_r ^ w_b.s_r,
                 t_l: 1,
@@ -230,17 +683,103 @@ pub fn generate_cw_from_seeds(
             u_b_i = w_b_next.u_l;
         }
 
-        cw_leaf[i] = share_leaf(z_a_i, z_b_i, alpha_bits[i], u_b_i);
+        // Going left when alpha goes right means x < alpha, and the other way around.
+        let value = match alpha_bits[i] {
+            1 => below,
+            _ => above,
+        };
+        cw_leaf[i] = share_leaf(z_a_i, z_b_i, value, u_b_i);
     }
-    cw_leaf[N * 8] = share_leaf(s_a_i as u32, s_b_i as u32, 1, t_b_i);
+    cw_leaf[n_bits] = share_leaf(G::from_block(s_a_i), G::from_block(s_b_i), equal, t_b_i);
     (cw, cw_leaf)
 }
 
-///
-/// Correction words logic
-///
+//
+// Serialization functions
+//
+
+/// Size of a correction word in the raw lines: seed, z value and 4 control bits.
+pub const fn cw_len<G: Group>() -> usize {
+    L + G::BYTES + 4
+}
+
+/// Size of a tree with `n_bits` levels in the raw lines: seed, correction words and leaves.
+pub const fn tree_len<G: Group>(n_bits: usize) -> usize {
+    L + cw_len::<G>() * n_bits + G::BYTES * (n_bits + 1)
+}
+
+/// Writes the seed, the correction words and the leaves of a tree,
+/// i.e. the first `tree_len(cw.len())` bytes of `array`.
+pub fn write_tree_to_array<G: Group>(
+    s: u128,
+    cw: &[CompressedCorrectionWord<G>],
+    cw_leaf: &[G],
+    array: &mut [u8],
+) {
+    let n_bits = cw.len();
+    let cw_len = cw_len::<G>();
+    assert_eq!(cw_leaf.len(), n_bits + 1);
+    array[0..L].copy_from_slice(&s.to_le_bytes());
+    for (i, cw_i) in cw.iter().enumerate() {
+        // Start index for the control word
+        let mut j = L + i * cw_len;
+
+        // Copy seeds first (u128)
+        array[j..j + L].copy_from_slice(&cw_i.s.to_le_bytes());
+        cw_i.z.write_le_bytes(&mut array[j + L..j + L + G::BYTES]);
+
+        // Copy control bits at the end (u8)
+        j = j + L + G::BYTES;
+        array[j] = cw_i.t_l;
+        array[j + 1] = cw_i.t_r;
+        array[j + 2] = cw_i.u_l;
+        array[j + 3] = cw_i.u_r;
+    }
+    for (i, leaf) in cw_leaf.iter().enumerate() {
+        let j = L + n_bits * cw_len + i * G::BYTES;
+        leaf.write_le_bytes(&mut array[j..j + G::BYTES]);
+    }
+}
+
+/// Reads a tree with `n_bits` levels written by `write_tree_to_array`.
+pub fn read_tree_from_array<G: Group>(
+    array: &[u8],
+    n_bits: usize,
+) -> (u128, Vec<CompressedCorrectionWord<G>>, Vec<G>) {
+    let cw_len = cw_len::<G>();
+    let s = u128::from_le_bytes(array[0..L].try_into().unwrap());
+
+    let mut cw = Vec::with_capacity(n_bits);
+    let mut cw_leaf = Vec::with_capacity(n_bits + 1);
+
+    for i in 0..n_bits {
+        let mut j = L + i * cw_len;
+
+        let s = u128::from_le_bytes(array[j..j + L].try_into().unwrap());
+        let z = G::read_le_bytes(&array[j + L..j + L + G::BYTES]);
+
+        j = j + L + G::BYTES;
+        cw.push(CompressedCorrectionWord {
+            s,
+            z,
+            t_l: array[j],
+            t_r: array[j + 1],
+            u_l: array[j + 2],
+            u_r: array[j + 3],
+        });
+    }
+    for i in 0..(n_bits + 1) {
+        let j = L + n_bits * cw_len + i * G::BYTES;
+        cw_leaf.push(G::read_le_bytes(&array[j..j + G::BYTES]));
+    }
+    (s, cw, cw_leaf)
+}
+
+//
+// Correction words logic
+//
 
-pub fn xor_2_words(u: &CorrectionWord, v: &CorrectionWord) -> CorrectionWord {
+pub fn xor_2_words<G: Group>(u: &CorrectionWord<G>, v: &CorrectionWord<G>) -> CorrectionWord<G> {
     CorrectionWord {
         s_l: u.s_l ^ v.s_l,
         t_l: u.t_l ^ v.t_l,
@@ -253,7 +792,11 @@ pub fn xor_2_words(u: &CorrectionWord, v: &CorrectionWord) -> CorrectionWord {
     }
 }
 
-fn xor_3_words(u: &CorrectionWord, v: &CorrectionWord, w: &CorrectionWord) -> CorrectionWord {
+fn xor_3_words<G: Group>(
+    u: &CorrectionWord<G>,
+    v: &CorrectionWord<G>,
+    w: &CorrectionWord<G>,
+) -> CorrectionWord<G> {
     CorrectionWord {
         s_l: u.s_l ^ v.s_l ^ w.s_l,
         t_l: u.t_l ^ v.t_l ^ w.t_l,
@@ -266,7 +809,7 @@ fn xor_3_words(u: &CorrectionWord, v: &CorrectionWord, w: &CorrectionWord) -> Co
     }
 }
 
-fn compress_word(w: &CorrectionWord, alpha_i: u8) -> CompressedCorrectionWord {
+fn compress_word<G: Group>(w: &CorrectionWord<G>, alpha_i: u8) -> CompressedCorrectionWord<G> {
     let (z, s) = match alpha_i {
         1 => (w.z_r, w.s_l),
         _ => (w.z_l, w.s_r),
@@ -281,7 +824,7 @@ fn compress_word(w: &CorrectionWord, alpha_i: u8) -> CompressedCorrectionWord {
     }
 }
 
-pub fn decompress_word(w: &CompressedCorrectionWord) -> CorrectionWord {
+pub fn decompress_word<G: Group>(w: &CompressedCorrectionWord<G>) -> CorrectionWord<G> {
     CorrectionWord {
         z_l: w.z,
         s_l: w.s,
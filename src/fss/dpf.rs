@@ -2,23 +2,64 @@
 //! DPF implementations
 //!
 
+use std::marker::PhantomData;
+
+use crate::fss::{eval_range_depth_first, Beta, One};
 use crate::stream::Prg;
-use crate::utils::{bit_decomposition_u32, compute_out, share_leaf};
-use crate::{L, N};
-use rand::Rng;
+use crate::utils::{bit_decomposition, compute_out, share_leaf, Domain, Group};
+use crate::L;
+use rand::{CryptoRng, Rng, RngCore};
+
+///
+/// Deterministic function.
+/// The correction words slices must have one entry per bit of alpha.
+/// The leaf correction word is in the output group G, with value beta on alpha.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn generate_cw_from_seeds<T: Domain, G: Group>(
+    prg: &mut impl Prg,
+    alpha: T,
+    beta: G,
+    s_a: u128,
+    s_b: u128,
+    cw: &mut [u128],
+    t_l: &mut [u8],
+    t_r: &mut [u8],
+) -> G {
+    let (s_a_n, s_b_n, t_b_n) = generate_path_cw(prg, alpha, s_a, s_b, cw, t_l, t_r);
+    // We only need the size of the group to make a sharing of beta
+    share_leaf(G::from_block(s_a_n), G::from_block(s_b_n), beta, t_b_n)
+}
 
 ///
-/// Deterministic function
+/// Deterministic function, fills the correction words along the path of alpha.
+/// Returns the seeds of Alice and Bob on the leaf alpha, and the control bit of Bob.
 ///
-pub fn generate_cw_from_seeds(
+pub fn generate_path_cw<T: Domain>(
     prg: &mut impl Prg,
-    alpha: u32,
+    alpha: T,
     s_a: u128,
     s_b: u128,
-    cw: &mut [u128; N * 8],
-    t_l: &mut [u8; N * 8],
-    t_r: &mut [u8; N * 8],
-) -> u32 {
+    cw: &mut [u128],
+    t_l: &mut [u8],
+    t_r: &mut [u8],
+) -> (u128, u128, u8) {
+    generate_path_cw_bits(prg, &bit_decomposition(alpha), s_a, s_b, cw, t_l, t_r)
+}
+
+///
+/// Same as `generate_path_cw`, for a tree with one level per bit of `alpha_bits`
+/// (most significant bits first), e.g. a tree smaller than the domain of T.
+///
+pub fn generate_path_cw_bits(
+    prg: &mut impl Prg,
+    alpha_bits: &[u8],
+    s_a: u128,
+    s_b: u128,
+    cw: &mut [u128],
+    t_l: &mut [u8],
+    t_r: &mut [u8],
+) -> (u128, u128, u8) {
     // Initialize control bits.
     let mut t_a_i = 0u8;
     let mut t_b_i = 1u8;
@@ -28,12 +69,17 @@ pub fn generate_cw_from_seeds(
     let mut s_b_i: u128 = s_b;
 
     // Iterate over the bits of alpha
-    let alpha_bits = bit_decomposition_u32(alpha);
+    assert_eq!(cw.len(), alpha_bits.len());
+    assert_eq!(t_l.len(), alpha_bits.len());
+    assert_eq!(t_r.len(), alpha_bits.len());
 
-    for i in 0..(N * 8) {
+    let mut blocks = [[0u128; 2]; 2];
+    for i in 0..alpha_bits.len() {
         // Keep only 1 bit instead of a byte for t_l and t_r (not optimal)
-        let (s_a_l, t_a_l, s_a_r, t_a_r) = g(prg, s_a_i);
-        let (s_b_l, t_b_l, s_b_r, t_b_r) = g(prg, s_b_i);
+        // Both paths are expanded at once.
+        prg.expand_batch(&[s_a_i, s_b_i], &mut blocks);
+        let (s_a_l, t_a_l, s_a_r, t_a_r) = g_from_blocks(blocks[0]);
+        let (s_b_l, t_b_l, s_b_r, t_b_r) = g_from_blocks(blocks[1]);
 
         // Keep left if a_i = 0, keep right if a_i = 1.
         let (s_a_keep, s_a_lose, t_a_keep) = match alpha_bits[i] {
@@ -76,17 +122,19 @@ pub fn generate_cw_from_seeds(
             t_b_i = t_b_keep ^ t_cw_keep;
         }
     }
-    // We only need 32 bits to make a sharing of 1
-    share_leaf(s_a_i as u32, s_b_i as u32, 1, t_b_i)
+    (s_a_i, s_b_i, t_b_i)
 }
 
 ///
-/// Wrapper around a Prg with expansion factor 2
+/// Wrapper around the first 2 blocks of the Prg, i.e. `P::EXPANSION >= 2`
 ///
 pub fn g(prg: &mut impl Prg, seed: u128) -> (u128, u8, u128, u8) {
-    assert_eq!(L, 128 / 8);
+    g_from_blocks(prg.expand_array(seed))
+}
 
-    let out = prg.expand(seed);
+/// Same as `g`, from blocks already expanded, e.g. by `Prg::expand_batch`.
+pub fn g_from_blocks(out: [u128; 2]) -> (u128, u8, u128, u8) {
+    assert_eq!(L, 128 / 8);
 
     let t_l = out[0] as u8 & 1u8;
     let t_r = out[1] as u8 & 1u8;
@@ -96,92 +144,454 @@ pub fn g(prg: &mut impl Prg, seed: u128) -> (u128, u8, u128, u8) {
     (s_l, t_l, s_r, t_r)
 }
 
-/// DPF Key for alpha in u32 given at Keygen time and beta = 1
+///
+/// Expands a leaf seed into `length` group elements, calling the Prg in counter mode
+/// on 2 blocks at a time when one expansion is not enough.
+///
+pub fn expand_leaf<G: Group>(prg: &mut impl Prg, seed: u128, length: usize) -> Vec<G> {
+    let bits_per_element = G::BYTES * 8;
+    let mut leaf = Vec::with_capacity(length);
+    let mut counter = 0u128;
+    while leaf.len() < length {
+        let blocks: [u128; 2] = prg.expand_array(seed ^ counter);
+        for block in blocks {
+            // Cut as many elements as possible from each 128 bits block.
+            for k in 0..(128 / bits_per_element) {
+                if leaf.len() == length {
+                    break;
+                }
+                leaf.push(G::from_block(block >> (k * bits_per_element)));
+            }
+        }
+        counter += 1;
+    }
+    leaf
+}
+
+/// DPF Key for alpha in T, with the payload B given at Keygen time.
+/// The tree has one level per bit of T, e.g. 32 levels for u32.
+/// The output is shared in G, e.g. Z/2^32Z for u32 or Z/2^64Z for u64.
 #[derive(Debug)]
-pub struct DPFKeyAlpha1 {
+pub struct DPFKey<T: Domain = u32, G: Group = u32, B = One> {
     pub s: u128,
-    pub cw: [u128; N * 8],
-    pub t_l: [u8; N * 8],
-    pub t_r: [u8; N * 8],
-    pub cw_leaf: u32,
+    pub cw: Vec<u128>,
+    pub t_l: Vec<u8>,
+    pub t_r: Vec<u8>,
+    pub cw_leaf: G,
+    domain: PhantomData<(T, B)>,
+}
+
+/// DPF Key for alpha in T given at Keygen time and beta = 1.
+pub type DPFKeyAlpha1<T = u32, G = u32> = DPFKey<T, G, One>;
+
+/// DPF Key for alpha in T and beta in G given at Keygen time.
+/// Same layout as `DPFKeyAlpha1`, only the leaf correction word changes.
+pub type DPFKeyAlphaBeta<T = u32, G = u32> = DPFKey<T, G, Beta>;
+
+/// DPF Key for alpha in T and a vector beta in G^m given at Keygen time.
+/// The leaf seed is expanded into m elements, so only the leaf correction word grows with m.
+#[derive(Debug)]
+pub struct DPFKeyAlphaVector<T: Domain = u32, G: Group = u32> {
+    pub s: u128,
+    pub cw: Vec<u128>,
+    pub t_l: Vec<u8>,
+    pub t_r: Vec<u8>,
+    pub cw_leaf: Vec<G>,
+    domain: PhantomData<T>,
 }
 
 pub trait DPFKey1: Sized {
-    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32;
+    type Input: Domain;
+    type Output: Group;
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: Self::Input) -> Self::Output;
+
+    /// Keys with the seeds drawn from `rng`.
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self);
+
+    fn generate_keypair(prg: &mut impl Prg, alpha: Self::Input) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, alpha, &mut rand::thread_rng())
+    }
+}
+
+pub trait DPFKeyBeta: Sized {
+    type Input: Domain;
+    type Output: Group;
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: Self::Input) -> Self::Output;
+
+    /// Keys with the seeds drawn from `rng`.
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        beta: Self::Output,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self);
+
+    fn generate_keypair(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        beta: Self::Output,
+    ) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, alpha, beta, &mut rand::thread_rng())
+    }
+}
+
+pub trait DPFKeyVector: Sized {
+    type Input: Domain;
+    type Output: Group;
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: Self::Input) -> Vec<Self::Output>;
+
+    /// Keys with the seeds drawn from `rng`.
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        beta: &[Self::Output],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self);
 
-    fn generate_keypair(prg: &mut impl Prg, alpha: u32) -> (Self, Self);
+    fn generate_keypair(
+        prg: &mut impl Prg,
+        alpha: Self::Input,
+        beta: &[Self::Output],
+    ) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, alpha, beta, &mut rand::thread_rng())
+    }
+}
+
+impl<T: Domain, G: Group> DPFKey1 for DPFKey<T, G, One> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: T,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        Self::keypair_with_beta(prg, alpha, G::one(), rng)
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        self.eval_leaf(prg, party_id, x)
+    }
 }
 
-impl DPFKey1 for DPFKeyAlpha1 {
-    fn generate_keypair(prg: &mut impl Prg, alpha: u32) -> (Self, Self) {
-        // Thread randomness for parallelization.
-        let mut rng = rand::thread_rng();
+impl<T: Domain, G: Group> DPFKeyBeta for DPFKey<T, G, Beta> {
+    type Input = T;
+    type Output = G;
 
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: T,
+        beta: G,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        Self::keypair_with_beta(prg, alpha, beta, rng)
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        self.eval_leaf(prg, party_id, x)
+    }
+}
+
+impl<T: Domain, G: Group> DPFKeyVector for DPFKeyAlphaVector<T, G> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: T,
+        beta: &[G],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
         // Initialize seeds.
         let s_a: u128 = rng.gen();
         let s_b: u128 = rng.gen();
 
         // Memory allocation for the correction words
-        let mut cw = [0u128; N * 8];
-        let mut t_l = [0u8; N * 8];
-        let mut t_r = [0u8; N * 8];
+        let mut cw = vec![0u128; T::N * 8];
+        let mut t_l = vec![0u8; T::N * 8];
+        let mut t_r = vec![0u8; T::N * 8];
+
+        let (s_a_n, s_b_n, t_b_n) =
+            generate_path_cw(prg, alpha, s_a, s_b, &mut cw, &mut t_l, &mut t_r);
 
-        let cw_leaf = generate_cw_from_seeds(prg, alpha, s_a, s_b, &mut cw, &mut t_l, &mut t_r);
+        // Share beta element-wise with the expanded leaves.
+        let leaf_a: Vec<G> = expand_leaf(prg, s_a_n, beta.len());
+        let leaf_b: Vec<G> = expand_leaf(prg, s_b_n, beta.len());
+        let mut cw_leaf = Vec::with_capacity(beta.len());
+        for j in 0..beta.len() {
+            cw_leaf.push(share_leaf(leaf_a[j], leaf_b[j], beta[j], t_b_n));
+        }
 
         // Return a key pair.
         (
-            DPFKeyAlpha1 {
+            DPFKeyAlphaVector {
                 s: s_a,
+                cw: cw.clone(),
+                t_l: t_l.clone(),
+                t_r: t_r.clone(),
+                cw_leaf: cw_leaf.clone(),
+                domain: PhantomData,
+            },
+            DPFKeyAlphaVector {
+                s: s_b,
                 cw,
                 t_l,
                 t_r,
                 cw_leaf,
+                domain: PhantomData,
+            },
+        )
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> Vec<G> {
+        let (s_n, t_n) = eval_path(prg, party_id, self.s, &self.cw, &self.t_l, &self.t_r, x);
+        let leaf: Vec<G> = expand_leaf(prg, s_n, self.cw_leaf.len());
+        leaf.iter()
+            .zip(self.cw_leaf.iter())
+            .map(|(mask, cw_leaf)| compute_out(*mask, *cw_leaf, t_n, party_id))
+            .collect()
+    }
+}
+
+impl<T: Domain, G: Group, B> DPFKey<T, G, B> {
+    // Keys sharing beta on alpha.
+    fn keypair_with_beta(
+        prg: &mut impl Prg,
+        alpha: T,
+        beta: G,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        // Initialize seeds.
+        let s_a: u128 = rng.gen();
+        let s_b: u128 = rng.gen();
+
+        // Memory allocation for the correction words
+        let mut cw = vec![0u128; T::N * 8];
+        let mut t_l = vec![0u8; T::N * 8];
+        let mut t_r = vec![0u8; T::N * 8];
+
+        let cw_leaf =
+            generate_cw_from_seeds(prg, alpha, beta, s_a, s_b, &mut cw, &mut t_l, &mut t_r);
+
+        // Return a key pair.
+        (
+            DPFKey {
+                s: s_a,
+                cw: cw.clone(),
+                t_l: t_l.clone(),
+                t_r: t_r.clone(),
+                cw_leaf,
+                domain: PhantomData,
             },
-            DPFKeyAlpha1 {
+            DPFKey {
                 s: s_b,
                 cw,
                 t_l,
                 t_r,
                 cw_leaf,
+                domain: PhantomData,
             },
         )
     }
 
-    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32 {
-        // Initialize the control bit and the seed.
-        assert!((party_id == 0u8) || (party_id == 1u8));
-        let mut t_i: u8 = party_id;
-        let mut s_i: u128 = self.s;
-
-        // Compare the bit decomposition of x with the special path.
-        let x_bits: Vec<u8> = bit_decomposition_u32(x);
-        for i in 0..(N * 8) {
-            let (s_l, t_l, s_r, t_r) = g(prg, s_i);
-            let s_cw = self.cw[i];
-            let t_cw_l = self.t_l[i];
-            let t_cw_r = self.t_r[i];
-
-            // We don't compute the XOR on the side that we don't keep.
-            if x_bits[i] == 0u8 {
-                // If x[i] = 0, keep left.
-                if t_i == 0u8 {
-                    s_i = s_l;
-                    t_i = t_l;
-                } else {
-                    s_i = s_l ^ s_cw;
-                    t_i = t_l ^ t_cw_l;
-                }
+    // Share of the leaf x.
+    fn eval_leaf(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        let (s_n, t_n) = eval_path(prg, party_id, self.s, &self.cw, &self.t_l, &self.t_r, x);
+        compute_out(G::from_block(s_n), self.cw_leaf, t_n, party_id)
+    }
+
+    /// Shares of the whole domain, `out` must have 2^(N * 8) elements.
+    pub fn eval_all<P: Prg + Clone + Sync>(&self, prg: &P, party_id: u8, out: &mut [G]) {
+        assert_eq!(out.len() as u128, 1u128 << (T::N * 8));
+        self.eval_range(prg, party_id, T::from_u64(0), out);
+    }
+
+    /// Shares on x = start, start + 1, ..., start + out.len() - 1, without wrapping around.
+    pub fn eval_range<P: Prg + Clone + Sync>(
+        &self,
+        prg: &P,
+        party_id: u8,
+        start: T,
+        out: &mut [G],
+    ) {
+        eval_range_into(
+            prg,
+            party_id,
+            self.s,
+            &self.cw,
+            &self.t_l,
+            &self.t_r,
+            self.cw_leaf,
+            start,
+            out,
+        );
+    }
+}
+
+///
+/// Walks down the tree along the bits of x.
+/// Returns the seed and the control bit on the leaf x.
+///
+pub fn eval_path<T: Domain>(
+    prg: &mut impl Prg,
+    party_id: u8,
+    s: u128,
+    cw: &[u128],
+    t_cw_l: &[u8],
+    t_cw_r: &[u8],
+    x: T,
+) -> (u128, u8) {
+    // Initialize the control bit and the seed.
+    assert!((party_id == 0u8) || (party_id == 1u8));
+    let mut t_i: u8 = party_id;
+    let mut s_i: u128 = s;
+
+    // Compare the bit decomposition of x with the special path.
+    let x_bits: Vec<u8> = bit_decomposition(x);
+    for i in 0..x_bits.len() {
+        let (s_l, t_l, s_r, t_r) = g(prg, s_i);
+
+        // We don't compute the XOR on the side that we don't keep.
+        if x_bits[i] == 0u8 {
+            // If x[i] = 0, keep left.
+            if t_i == 0u8 {
+                s_i = s_l;
+                t_i = t_l;
             } else {
-                // If x[i] = 1, keep right.
-                if t_i == 0u8 {
-                    s_i = s_r;
-                    t_i = t_r;
-                } else {
-                    s_i = s_r ^ s_cw;
-                    t_i = t_r ^ t_cw_r;
-                }
+                s_i = s_l ^ cw[i];
+                t_i = t_l ^ t_cw_l[i];
             }
+        } else {
+            // If x[i] = 1, keep right.
+            if t_i == 0u8 {
+                s_i = s_r;
+                t_i = t_r;
+            } else {
+                s_i = s_r ^ cw[i];
+                t_i = t_r ^ t_cw_r[i];
+            }
+        }
+    }
+    (s_i, t_i)
+}
+
+/// Seed and correction words (cw, t_l and t_r) of a DPF tree, e.g. for `eval_paths`.
+pub type DPFTree<'a> = (u128, &'a [u128], &'a [u8], &'a [u8]);
+
+///
+/// Same as `eval_path` on xs[j] with the j-th tree,
+/// writes the seed and the control bit on each leaf in out.
+/// The paths are walked in lockstep, so each level is a single call to `Prg::expand_batch`.
+///
+pub fn eval_paths<T: Domain>(
+    prg: &mut impl Prg,
+    party_id: u8,
+    trees: &[DPFTree],
+    xs: &[T],
+    out: &mut [(u128, u8)],
+) {
+    assert!((party_id == 0u8) || (party_id == 1u8));
+    assert_eq!(trees.len(), xs.len());
+    assert_eq!(trees.len(), out.len());
+    let n_bits = T::N * 8;
+    assert!(trees.iter().all(|tree| tree.1.len() == n_bits));
+
+    let mut s: Vec<u128> = trees.iter().map(|tree| tree.0).collect();
+    let mut blocks = vec![[0u128; 2]; trees.len()];
+    for out_j in out.iter_mut() {
+        out_j.1 = party_id;
+    }
+    for i in 0..n_bits {
+        prg.expand_batch(&s, &mut blocks);
+        for (j, (_, cw, t_cw_l, t_cw_r)) in trees.iter().enumerate() {
+            let (s_l, t_l, s_r, t_r) = g_from_blocks(blocks[j]);
+            let t_i = out[j].1;
+            let (s_n, t_n) = match (xs[j].to_u64() >> (n_bits - 1 - i)) & 1 {
+                0 if t_i == 0 => (s_l, t_l),
+                0 => (s_l ^ cw[i], t_l ^ t_cw_l[i]),
+                _ if t_i == 0 => (s_r, t_r),
+                _ => (s_r ^ cw[i], t_r ^ t_cw_r[i]),
+            };
+            s[j] = s_n;
+            out[j].1 = t_n;
         }
-        compute_out(s_i as u32, self.cw_leaf, t_i, party_id)
     }
+    for (out_j, s_n) in out.iter_mut().zip(s) {
+        out_j.0 = s_n;
+    }
+}
+
+//
+// Full domain evaluation
+//
+
+///
+/// Evaluates the tree on every x in [start, start + out.len()) and writes the shares in out.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn eval_range_into<T: Domain, G: Group, P: Prg + Clone + Sync>(
+    prg: &P,
+    party_id: u8,
+    s: u128,
+    cw: &[u128],
+    t_cw_l: &[u8],
+    t_cw_r: &[u8],
+    cw_leaf: G,
+    start: T,
+    out: &mut [G],
+) {
+    assert_eq!(cw.len(), T::N * 8);
+    eval_range_into_depth(
+        prg,
+        party_id,
+        s,
+        cw,
+        t_cw_l,
+        t_cw_r,
+        cw_leaf,
+        start.to_u64(),
+        out,
+    );
+}
+
+///
+/// Same as `eval_range_into`, for a tree with one level per correction word.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn eval_range_into_depth<G: Group, P: Prg + Clone + Sync>(
+    prg: &P,
+    party_id: u8,
+    s: u128,
+    cw: &[u128],
+    t_cw_l: &[u8],
+    t_cw_r: &[u8],
+    cw_leaf: G,
+    start: u64,
+    out: &mut [G],
+) {
+    assert!((party_id == 0u8) || (party_id == 1u8));
+
+    // Nodes are (seed, control bit).
+    let seed = |node: &(u128, u8)| node.0;
+    let expand = |_: &mut P, blocks: [u128; 2], node: &(u128, u8), level: usize| {
+        let t_i = node.1;
+        let (mut s_l, mut t_l, mut s_r, mut t_r) = g_from_blocks(blocks);
+        if t_i == 1 {
+            s_l ^= cw[level];
+            t_l ^= t_cw_l[level];
+            s_r ^= cw[level];
+            t_r ^= t_cw_r[level];
+        }
+        ((s_l, t_l), (s_r, t_r))
+    };
+    let leaf = |node: &(u128, u8)| compute_out(G::from_block(node.0), cw_leaf, node.1, party_id);
+
+    eval_range_depth_first(prg, (s, party_id), cw.len(), start, out, seed, expand, leaf);
 }
@@ -0,0 +1,173 @@
+//!
+//! Half-Tree DPF (https://eprint.iacr.org/2022/1431), with one hash call per level
+//!
+
+use std::marker::PhantomData;
+
+use crate::fss::dpf::DPFKey1;
+use crate::fss::eval_range_depth_first;
+use crate::stream::Prg;
+use crate::utils::{bit_decomposition, compute_out, share_leaf, Domain, Group};
+use rand::{CryptoRng, Rng, RngCore};
+
+/// Half-Tree DPF Key for alpha in T given at Keygen time and beta = 1.
+/// Same interface as `DPFKeyAlpha1`, with one correction word of 128 bits per level
+/// and no control bit correction words: the control bit is the last bit of the seed.
+/// The roots of the two parties differ by a secret offset delta, with an odd delta.
+/// Each node s has children H(s) and H(s) ^ s, so the seeds stay equal off the path
+/// and differ by delta on the path.
+#[derive(Debug)]
+pub struct HalfTreeDPFKey<T: Domain = u32, G: Group = u32> {
+    pub s: u128,
+    pub cw: Vec<u128>,
+    pub cw_leaf: G,
+    domain: PhantomData<T>,
+}
+
+impl<T: Domain, G: Group> DPFKey1 for HalfTreeDPFKey<T, G> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        alpha: T,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        // Initialize the offset and the seeds, the control bits of the roots differ.
+        let delta: u128 = rng.gen::<u128>() | 1;
+        let s_a: u128 = rng.gen();
+        let s_b: u128 = s_a ^ delta;
+
+        // Seeds at level i.
+        let mut s_a_i = s_a;
+        let mut s_b_i = s_b;
+
+        let alpha_bits = bit_decomposition(alpha);
+        let mut cw = Vec::with_capacity(alpha_bits.len());
+        for alpha_i in alpha_bits {
+            let h_a = hash(prg, s_a_i);
+            let h_b = hash(prg, s_b_i);
+
+            // The left children differ by delta if alpha_i = 0 and are equal otherwise,
+            // so the right children are equal if alpha_i = 0 and differ by delta otherwise.
+            let cw_i = match alpha_i {
+                0u8 => h_a ^ h_b ^ delta,
+                _ => h_a ^ h_b,
+            };
+            s_a_i = child(s_a_i, h_a, cw_i, alpha_i);
+            s_b_i = child(s_b_i, h_b, cw_i, alpha_i);
+            cw.push(cw_i);
+        }
+
+        // The leaf seeds are correlated, hash them once more for the output masks.
+        let mask_a = G::from_block(hash(prg, s_a_i));
+        let mask_b = G::from_block(hash(prg, s_b_i));
+        let cw_leaf = share_leaf(mask_a, mask_b, G::one(), control_bit(s_b_i));
+
+        // Return a key pair.
+        (
+            HalfTreeDPFKey {
+                s: s_a,
+                cw: cw.clone(),
+                cw_leaf,
+                domain: PhantomData,
+            },
+            HalfTreeDPFKey {
+                s: s_b,
+                cw,
+                cw_leaf,
+                domain: PhantomData,
+            },
+        )
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        assert!((party_id == 0u8) || (party_id == 1u8));
+        let mut s_i = self.s;
+        for (x_i, cw_i) in bit_decomposition(x).into_iter().zip(self.cw.iter()) {
+            s_i = child(s_i, hash(prg, s_i), *cw_i, x_i);
+        }
+        let mask = G::from_block(hash(prg, s_i));
+        compute_out(mask, self.cw_leaf, control_bit(s_i), party_id)
+    }
+}
+
+impl<T: Domain, G: Group> HalfTreeDPFKey<T, G> {
+    /// Shares of the whole domain, `out` must have 2^(N * 8) elements.
+    pub fn eval_all<P: Prg + Clone + Sync>(&self, prg: &P, party_id: u8, out: &mut [G]) {
+        assert_eq!(out.len() as u128, 1u128 << (T::N * 8));
+        self.eval_range(prg, party_id, T::from_u64(0), out);
+    }
+
+    /// Shares on x = start, start + 1, ..., start + out.len() - 1, without wrapping around.
+    /// Both children of a node share the same hash call.
+    pub fn eval_range<P: Prg + Clone + Sync>(
+        &self,
+        prg: &P,
+        party_id: u8,
+        start: T,
+        out: &mut [G],
+    ) {
+        assert!((party_id == 0u8) || (party_id == 1u8));
+        let depth = self.cw.len();
+
+        // Nodes are (seed, hash of the seed on the last level).
+        let seed = |node: &(u128, u128)| sigma(node.0);
+        let expand = |prg: &mut P, [h]: [u128; 1], node: &(u128, u128), level: usize| {
+            let s_i = node.0;
+            let s_l = child(s_i, h, self.cw[level], 0);
+            let s_r = child(s_i, h, self.cw[level], 1);
+            if level + 1 == depth {
+                let mut hashes = [[0u128; 1]; 2];
+                prg.expand_batch(&[sigma(s_l), sigma(s_r)], &mut hashes);
+                ((s_l, hashes[0][0]), (s_r, hashes[1][0]))
+            } else {
+                ((s_l, 0), (s_r, 0))
+            }
+        };
+        let leaf = |node: &(u128, u128)| {
+            compute_out(
+                G::from_block(node.1),
+                self.cw_leaf,
+                control_bit(node.0),
+                party_id,
+            )
+        };
+        let root = (self.s, 0);
+        eval_range_depth_first(prg, root, depth, start.to_u64(), out, seed, expand, leaf);
+    }
+}
+
+// The last bit of the seed.
+fn control_bit(s: u128) -> u8 {
+    s as u8 & 1u8
+}
+
+// Left child H(s) ^ t * cw, and right child the left child ^ s.
+fn child(s: u128, h: u128, cw: u128, x_i: u8) -> u128 {
+    let mut left = h;
+    if control_bit(s) == 1 {
+        left ^= cw;
+    }
+    match x_i {
+        0u8 => left,
+        _ => left ^ s,
+    }
+}
+
+///
+/// Circular correlation robust hash H(s) = pi(sigma(s)) ^ sigma(s),
+/// with the first block of the Prg (i.e. fixed-key AES for Mmo) and the orthomorphism
+/// sigma(s_L || s_R) = (s_L ^ s_R) || s_L.
+///
+pub fn hash(prg: &mut impl Prg, s: u128) -> u128 {
+    let [h]: [u128; 1] = prg.expand_array(sigma(s));
+    h
+}
+
+// The orthomorphism sigma(s_L || s_R) = (s_L ^ s_R) || s_L.
+fn sigma(s: u128) -> u128 {
+    let high = s >> 64;
+    let low = s & (u64::MAX as u128);
+    ((high ^ low) << 64) | high
+}
@@ -1,2 +1,185 @@
+use rayon::prelude::*;
+
+use crate::stream::Prg;
+
+pub mod dcf;
 pub mod dif;
 pub mod dpf;
+pub mod halftree;
+pub mod mpdpf;
+
+/// Payload of the keys sharing 1 on their point or interval, e.g. `DIFKeyAlpha1`.
+#[derive(Debug)]
+pub struct One;
+
+/// Payload of the keys sharing a beta given at Keygen time, e.g. `DIFKeyAlphaBeta`.
+#[derive(Debug)]
+pub struct Beta;
+
+/// Minimum number of subtrees per thread, to balance the work when the range is not aligned.
+const SUBTREES_PER_THREAD: usize = 4;
+
+/// Smaller ranges are evaluated on the calling thread, e.g. small lookup tables.
+const MIN_PARALLEL_LEAVES: usize = 1 << 12;
+
+/// Number of levels at the bottom of the tree expanded breadth-first, i.e. batches of up to
+/// 8 nodes for `Prg::expand_batch`.
+const BATCH_LEVELS: usize = 3;
+
+// Nodes of a level tagged with their prefix, i.e. the first bits of x,
+// and the buffers to expand them with a single call to the Prg.
+struct Level<N, const K: usize> {
+    nodes: Vec<(u128, N)>,
+    next: Vec<(u128, N)>,
+    seeds: Vec<u128>,
+    blocks: Vec<[u128; K]>,
+}
+
+impl<N, const K: usize> Level<N, K> {
+    fn with_capacity(capacity: usize) -> Self {
+        Level {
+            nodes: Vec::with_capacity(capacity),
+            next: Vec::with_capacity(capacity),
+            seeds: Vec::with_capacity(capacity),
+            blocks: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+///
+/// Evaluates a tree of depth `n_bits` on every x in [start, start + out.len()).
+/// Each interior node is expanded only once. The first levels are expanded sequentially,
+/// level by level, until there are enough subtrees to feed the rayon threads (small ranges
+/// are not split at all). Each subtree is then walked depth-first with a stack of
+/// O(n_bits) nodes, and its leaves are written directly into its chunk of `out`.
+/// The levels are expanded with `Prg::expand_batch`, and so are the last `BATCH_LEVELS`
+/// levels below each node of the depth-first walk.
+///
+/// `seed` gives the input of the Prg for a node, and `expand` returns the left and right
+/// children of a node at some level from the K blocks of its expansion.
+/// `leaf` maps the nodes of the last level to the output.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn eval_range_depth_first<N, G, P, S, E, F, const K: usize>(
+    prg: &P,
+    root: N,
+    n_bits: usize,
+    start: u64,
+    out: &mut [G],
+    seed: S,
+    expand: E,
+    leaf: F,
+) where
+    N: Copy + Send,
+    G: Send,
+    P: Prg + Clone + Sync,
+    S: Fn(&N) -> u128 + Sync,
+    E: Fn(&mut P, [u128; K], &N, usize) -> (N, N) + Sync,
+    F: Fn(&N) -> G + Sync,
+{
+    let lo = start as u128;
+    let hi = lo + out.len() as u128;
+    assert!(hi <= 1u128 << n_bits, "The range goes beyond the domain");
+    if out.is_empty() {
+        return;
+    }
+
+    // Only the children with some leaves in the range are kept.
+    let in_range = |prefix: u128, level: usize| {
+        let (child_lo, child_hi) = subtree_range(prefix, level, n_bits, (lo, hi));
+        child_lo < child_hi
+    };
+    let expand_level = |prg: &mut P, level_nodes: &mut Level<N, K>, level: usize| {
+        let Level {
+            nodes,
+            next,
+            seeds,
+            blocks,
+        } = level_nodes;
+        seeds.clear();
+        seeds.extend(nodes.iter().map(|(_, node)| seed(node)));
+        blocks.resize(seeds.len(), [0u128; K]);
+        prg.expand_batch(seeds, blocks);
+        next.clear();
+        for ((prefix, node), blocks) in nodes.iter().zip(blocks.iter()) {
+            let (left, right) = expand(prg, *blocks, node, level);
+            for &(child, node) in [(prefix << 1, left), ((prefix << 1) | 1, right)].iter() {
+                if in_range(child, level + 1) {
+                    next.push((child, node));
+                }
+            }
+        }
+        std::mem::swap(nodes, next);
+    };
+
+    // Sequential breadth-first expansion of the first levels.
+    let mut level = 0;
+    let n_subtrees = match out.len() < MIN_PARALLEL_LEAVES {
+        true => 1,
+        false => SUBTREES_PER_THREAD * rayon::current_num_threads(),
+    };
+    let mut top = Level::with_capacity(2 * n_subtrees);
+    top.nodes.push((0u128, root));
+    let mut main_prg = prg.clone();
+    while level < n_bits && top.nodes.len() < n_subtrees {
+        expand_level(&mut main_prg, &mut top, level);
+        level += 1;
+    }
+    let frontier = top.nodes;
+
+    // Split the output buffer: each subtree covers a contiguous chunk of the range.
+    let mut chunks: Vec<&mut [G]> = Vec::with_capacity(frontier.len());
+    let mut rest = out;
+    for (prefix, _) in frontier.iter() {
+        let (chunk_lo, chunk_hi) = subtree_range(*prefix, level, n_bits, (lo, hi));
+        let (chunk, tail) = rest.split_at_mut((chunk_hi - chunk_lo) as usize);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    // Each thread walks its subtree depth-first with its own Prg, left child first,
+    // down to the last levels that are expanded breadth-first.
+    let bottom = level.max(n_bits.saturating_sub(BATCH_LEVELS));
+    let finish = |((prefix, node), chunk): ((u128, N), &mut [G])| {
+        let mut prg = prg.clone();
+        let (chunk_lo, _) = subtree_range(prefix, level, n_bits, (lo, hi));
+        let mut batch = Level::with_capacity(1 << (n_bits - bottom));
+        let mut stack = Vec::with_capacity(bottom - level + 1);
+        stack.push((prefix, level, node));
+        while let Some((prefix, i, node)) = stack.pop() {
+            if i == bottom {
+                batch.nodes.clear();
+                batch.nodes.push((prefix, node));
+                for j in bottom..n_bits {
+                    expand_level(&mut prg, &mut batch, j);
+                }
+                for (prefix, node) in batch.nodes.iter() {
+                    chunk[(prefix - chunk_lo) as usize] = leaf(node);
+                }
+                continue;
+            }
+            let blocks = prg.expand_array(seed(&node));
+            let (left, right) = expand(&mut prg, blocks, &node, i);
+            for &(child, node) in [((prefix << 1) | 1, right), (prefix << 1, left)].iter() {
+                if in_range(child, i + 1) {
+                    stack.push((child, i + 1, node));
+                }
+            }
+        }
+    };
+    if frontier.len() == 1 {
+        frontier.into_iter().zip(chunks).for_each(finish);
+    } else {
+        frontier
+            .into_par_iter()
+            .zip(chunks.into_par_iter())
+            .for_each(finish);
+    }
+}
+
+/// Intersection of the leaves below a node at some level with the range.
+fn subtree_range(prefix: u128, level: usize, n_bits: usize, range: (u128, u128)) -> (u128, u128) {
+    let first_leaf = prefix << (n_bits - level);
+    let last_leaf = (prefix + 1) << (n_bits - level);
+    (first_leaf.max(range.0), last_leaf.min(range.1))
+}
@@ -0,0 +1,266 @@
+//!
+//! Multi-point DPF for sparse vectors, with cuckoo hashing into buckets of small DPFs
+//! (https://eprint.iacr.org/2018/707, Section 5)
+//!
+
+use std::marker::PhantomData;
+
+use rand::{CryptoRng, Rng, RngCore};
+use rayon::prelude::*;
+
+use crate::fss::dpf::{eval_range_into_depth, generate_path_cw_bits};
+use crate::stream::Prg;
+use crate::utils::{share_leaf, Domain, Group};
+
+/// Number of hash functions, i.e. of buckets holding each point of the domain.
+pub const N_HASHES: usize = 3;
+
+/// Largest supported domain for the full domain evaluation, with 2^32 points.
+/// On 32-bit targets, the domain must also have fewer than 2^32 points.
+pub const MAX_LOG_DOMAIN: usize = 32;
+
+// Cuckoo insertion attempts before picking new hash functions.
+const MAX_EVICTIONS: usize = 512;
+
+// Rounds of the Feistel network of the hash functions.
+const FEISTEL_ROUNDS: u64 = 4;
+
+/// DPF Key for one bucket: a tree with one level per bit of the bucket size.
+#[derive(Debug, Clone)]
+pub struct BucketKey<G: Group = u32> {
+    pub s: u128,
+    pub cw: Vec<u128>,
+    pub t_l: Vec<u8>,
+    pub t_r: Vec<u8>,
+    pub cw_leaf: G,
+}
+
+/// Multi-point DPF Key for t points (alpha_i, beta_i) in a domain of 2^log_domain elements of T.
+/// Hash j is a pseudorandom permutation pi_j of the domain, and with B buckets of
+/// `N_HASHES * m` positions, where m = ceil(2^log_domain / B), x is at position
+/// j * m + pi_j(x) / B of bucket pi_j(x) % B. Each alpha_i is placed in one of its buckets
+/// with cuckoo hashing, so keygen only hashes the alphas. The buckets hold about
+/// 2 * 2^log_domain / t positions each, so their trees are about log2(t) levels shorter
+/// than a DPF on the whole domain.
+#[derive(Debug)]
+pub struct MultiPointDPFKey<T: Domain = u32, G: Group = u32> {
+    pub log_domain: u8,
+    pub hash_seed: u64,
+    pub buckets: Vec<BucketKey<G>>,
+    domain: PhantomData<T>,
+}
+
+impl<T: Domain, G: Group> MultiPointDPFKey<T, G> {
+    /// Keys sharing beta_i on each alpha_i and 0 elsewhere. The alpha_i must be distinct.
+    pub fn generate_keypair(prg: &mut impl Prg, log_domain: u8, points: &[(T, G)]) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, log_domain, points, &mut rand::thread_rng())
+    }
+
+    /// Same as `generate_keypair`, with all the randomness drawn from `rng`.
+    pub fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        log_domain: u8,
+        points: &[(T, G)],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        assert!(log_domain as usize <= (T::N * 8).min(MAX_LOG_DOMAIN));
+        assert!(log_domain > 0);
+        assert!(
+            (log_domain as u32) < usize::BITS,
+            "The domain must be indexed by usize"
+        );
+        assert!(!points.is_empty());
+        let alphas: Vec<u64> = points.iter().map(|(alpha, _)| alpha.to_u64()).collect();
+        assert!(alphas.iter().all(|&alpha| alpha >> log_domain == 0));
+        let mut sorted_alphas = alphas.clone();
+        sorted_alphas.sort_unstable();
+        assert!(
+            sorted_alphas.windows(2).all(|w| w[0] != w[1]),
+            "The alphas must be distinct"
+        );
+
+        // Load factor 2/3, the insertion only fails with small probability.
+        // Note: the retries make the public hash_seed depend on the alphas, since a seed is only
+        // kept if it places them. This leaks little, as a seed fails with that small probability.
+        let n_buckets = points.len() + points.len() / 2 + 1;
+        let (hash_seed, placement) = loop {
+            let hash_seed: u64 = rng.gen();
+            let hashes = |x: u64, j: usize| locate(hash_seed, log_domain, n_buckets, j, x);
+            if let Some(placement) = cuckoo_insert(rng, hashes, n_buckets, &alphas) {
+                break (hash_seed, placement);
+            }
+        };
+        let depth = bucket_depth(N_HASHES * bucket_width(log_domain, n_buckets));
+
+        let mut buckets_a = Vec::with_capacity(n_buckets);
+        let mut buckets_b = Vec::with_capacity(n_buckets);
+        for point in placement.iter() {
+            // Empty buckets share 0, anywhere in the bucket.
+            let (position, beta) = match point {
+                Some((i, j)) => (
+                    locate(hash_seed, log_domain, n_buckets, *j, alphas[*i]).1 as u64,
+                    points[*i].1,
+                ),
+                None => (0, G::zero()),
+            };
+            let position_bits: Vec<u8> = (0..depth)
+                .rev()
+                .map(|j| (position >> j) as u8 & 1)
+                .collect();
+
+            // Initialize seeds.
+            let s_a: u128 = rng.gen();
+            let s_b: u128 = rng.gen();
+
+            // Memory allocation for the correction words
+            let mut cw = vec![0u128; depth];
+            let mut t_l = vec![0u8; depth];
+            let mut t_r = vec![0u8; depth];
+
+            let (s_a_n, s_b_n, t_b_n) =
+                generate_path_cw_bits(prg, &position_bits, s_a, s_b, &mut cw, &mut t_l, &mut t_r);
+            let cw_leaf = share_leaf(G::from_block(s_a_n), G::from_block(s_b_n), beta, t_b_n);
+
+            buckets_a.push(BucketKey {
+                s: s_a,
+                cw: cw.clone(),
+                t_l: t_l.clone(),
+                t_r: t_r.clone(),
+                cw_leaf,
+            });
+            buckets_b.push(BucketKey {
+                s: s_b,
+                cw,
+                t_l,
+                t_r,
+                cw_leaf,
+            });
+        }
+
+        // Return a key pair.
+        (
+            MultiPointDPFKey {
+                log_domain,
+                hash_seed,
+                buckets: buckets_a,
+                domain: PhantomData,
+            },
+            MultiPointDPFKey {
+                log_domain,
+                hash_seed,
+                buckets: buckets_b,
+                domain: PhantomData,
+            },
+        )
+    }
+
+    /// Shares of the whole domain, `out` must have 2^log_domain elements.
+    /// The buckets are evaluated in parallel, then each point adds up the shares at its
+    /// `N_HASHES` positions, found by hashing the point.
+    pub fn eval_all<P: Prg + Clone + Sync>(&self, prg: &P, party_id: u8, out: &mut [G]) {
+        assert_eq!(out.len() as u64, 1u64 << self.log_domain);
+        let n_buckets = self.buckets.len();
+        let bucket_len = N_HASHES * bucket_width(self.log_domain, n_buckets);
+
+        let mut shares = vec![G::zero(); n_buckets * bucket_len];
+        shares
+            .par_chunks_mut(bucket_len)
+            .zip(self.buckets.par_iter())
+            .for_each(|(shares, bucket)| {
+                eval_range_into_depth(
+                    prg,
+                    party_id,
+                    bucket.s,
+                    &bucket.cw,
+                    &bucket.t_l,
+                    &bucket.t_r,
+                    bucket.cw_leaf,
+                    0,
+                    shares,
+                );
+            });
+
+        out.par_iter_mut().enumerate().for_each(|(x, y)| {
+            *y = (0..N_HASHES).fold(G::zero(), |acc, j| {
+                let (b, position) = locate(self.hash_seed, self.log_domain, n_buckets, j, x as u64);
+                acc.add(shares[b * bucket_len + position])
+            });
+        });
+    }
+}
+
+// Public pseudorandom permutation pi_j of [0, 2^log_domain), from the seed shared by both keys:
+// a balanced Feistel network on an even number of bits, with cycle walking.
+fn permute(hash_seed: u64, j: usize, log_domain: u8, x: u64) -> u64 {
+    let half = (log_domain as u32).div_ceil(2);
+    let half_mask = (1u64 << half) - 1;
+    let mut y = x;
+    loop {
+        let (mut left, mut right) = (y >> half, y & half_mask);
+        for round in 0..FEISTEL_ROUNDS {
+            let key = hash_seed ^ ((j as u64) << 8 | round).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            let f = mix(key.wrapping_add(right)) & half_mask;
+            (left, right) = (right, left ^ f);
+        }
+        y = (left << half) | right;
+        if y >> log_domain == 0 {
+            return y;
+        }
+    }
+}
+
+// SplitMix64 finalizer.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Number of positions of a bucket for each hash function, i.e. ceil(2^log_domain / n_buckets).
+// Computed in u64, since 2^log_domain may not fit in usize.
+fn bucket_width(log_domain: u8, n_buckets: usize) -> usize {
+    (1u64 << log_domain).div_ceil(n_buckets as u64) as usize
+}
+
+// Bucket of x for hash j, and its position in the bucket.
+fn locate(hash_seed: u64, log_domain: u8, n_buckets: usize, j: usize, x: u64) -> (usize, usize) {
+    let y = permute(hash_seed, j, log_domain, x) as usize;
+    let width = bucket_width(log_domain, n_buckets);
+    (y % n_buckets, j * width + y / n_buckets)
+}
+
+// Cuckoo hashing of the alphas, with at most one alpha per bucket.
+// Returns the index of the alpha in each bucket and the hash function that placed it,
+// or None if the insertion fails.
+fn cuckoo_insert(
+    rng: &mut impl Rng,
+    hashes: impl Fn(u64, usize) -> (usize, usize),
+    n_buckets: usize,
+    alphas: &[u64],
+) -> Option<Vec<Option<(usize, usize)>>> {
+    let mut placement: Vec<Option<(usize, usize)>> = vec![None; n_buckets];
+    for i in 0..alphas.len() {
+        let mut current = i;
+        let mut inserted = false;
+        for _ in 0..MAX_EVICTIONS {
+            let buckets: [usize; N_HASHES] = std::array::from_fn(|j| hashes(alphas[current], j).0);
+            if let Some(j) = (0..N_HASHES).find(|&j| placement[buckets[j]].is_none()) {
+                placement[buckets[j]] = Some((current, j));
+                inserted = true;
+                break;
+            }
+            // Evict the alpha of a random bucket, and insert it somewhere else.
+            let j = rng.gen_range(0, N_HASHES);
+            current = placement[buckets[j]].replace((current, j)).unwrap().0;
+        }
+        if !inserted {
+            return None;
+        }
+    }
+    Some(placement)
+}
+
+// Number of levels for a bucket with some points.
+fn bucket_depth(n_points: usize) -> usize {
+    (usize::BITS - n_points.saturating_sub(1).leading_zeros()) as usize
+}
@@ -0,0 +1,315 @@
+//!
+//! Interval containment keys, for one or many intervals, following the IC and MIC gates of Boyle et al.
+//! (https://eprint.iacr.org/2020/1392)
+//!
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use std::slice;
+
+use crate::fss::dif::{
+    eval_path, eval_points, generate_cw_from_seeds, read_tree_from_array, tree_len,
+    write_tree_to_array, CompressedCorrectionWord,
+};
+use crate::stream::{Prg, RawKey};
+use crate::utils::{
+    random_domain_element, read_domain_element, wrapping_add_domain, wrapping_sub_domain,
+    write_domain_element, Domain, Group,
+};
+
+/// Interval containment key for inputs in T, with public bounds `lower <= upper`.
+/// A single DIF key is evaluated twice on the masked input.
+/// The output is shared in G.
+#[derive(Debug)]
+pub struct GenericIntervalKey<T: Domain, G: Group = u32> {
+    pub alpha_share: T,
+    pub lower: T,
+    pub upper: T,
+    pub z: G,
+    pub s: u128,
+    pub cw: Vec<CompressedCorrectionWord<G>>,
+    pub cw_leaf: Vec<G>,
+}
+
+/// Interval containment key on 32 bits, used by the FFI.
+pub type IntervalKey = GenericIntervalKey<u32>;
+
+impl<T: Domain, G: Group> RawKey for GenericIntervalKey<T, G> {
+    // 3 * N + G + 16 + 24 * (N * 8) + 4 * (N * 8 + 1), i.e. 932 bytes for u32
+    const KEY_LEN: usize = 3 * T::N + G::BYTES + tree_len::<G>(T::N * 8);
+
+    unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
+        // Get a mutable reference to the output line.
+        let out_ref: &mut [u8] = slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN);
+        // Write the key.
+        write_key_to_array(self, out_ref);
+    }
+
+    unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
+        read_key_from_array(slice::from_raw_parts(key_pointer, Self::KEY_LEN))
+    }
+}
+
+impl<T: Domain, G: Group> GenericIntervalKey<T, G> {
+    /// Keys for the indicator of x in [lower, upper], evaluated on x + alpha
+    /// where alpha is a random mask shared between the keys.
+    pub fn generate_keypair(prg: &mut impl Prg, lower: T, upper: T) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, lower, upper, &mut rand::thread_rng())
+    }
+
+    /// Same as `generate_keypair`, with all the randomness drawn from `rng`.
+    pub fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        lower: T,
+        upper: T,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        assert!(lower <= upper);
+
+        // Random mask of the input.
+        let alpha: T = random_domain_element(rng);
+
+        // Initialize seeds.
+        let s_a: u128 = rng.gen();
+        let s_b: u128 = rng.gen();
+
+        // The gate uses 1[y < alpha - 1], i.e. 1[y <= alpha - 2].
+        let gamma = wrapping_sub_domain(alpha, T::from_u64(2));
+        let (cw, cw_leaf) = generate_cw_from_seeds(prg, gamma, G::one(), s_a, s_b);
+
+        // Public correction term, depending on the wrap-arounds of the shifted bounds.
+        let z: G = correction_term(alpha, lower, upper);
+
+        // Secret-share alpha and z between Alice and Bob.
+        let mask: T = random_domain_element(rng);
+        let z_mask = G::from_block(rng.gen());
+
+        // Return a key pair.
+        (
+            GenericIntervalKey {
+                alpha_share: wrapping_sub_domain(alpha, mask),
+                lower,
+                upper,
+                z: z.sub(z_mask),
+                s: s_a,
+                cw: cw.clone(),
+                cw_leaf: cw_leaf.clone(),
+            },
+            GenericIntervalKey {
+                alpha_share: mask,
+                lower,
+                upper,
+                z: z_mask,
+                s: s_b,
+                cw,
+                cw_leaf,
+            },
+        )
+    }
+
+    /// Share of 1[lower <= x <= upper], where x + alpha is the public input.
+    pub fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        assert!((party_id == 0u8) || (party_id == 1u8));
+        let (x_p, x_q1) = shifted_inputs(x, self.lower, self.upper);
+        let s_p = eval_path(prg, party_id, self.s, &self.cw, &self.cw_leaf, x_p);
+        let s_q1 = eval_path(prg, party_id, self.s, &self.cw, &self.cw_leaf, x_q1);
+        combine_shares(party_id, x, self.lower, self.upper, self.z, s_p, s_q1)
+    }
+}
+
+/// Multiple interval containment key, for m public intervals and a single mask.
+/// All the intervals share one DIF key, and eval walks its tree once for the 2m points.
+/// The output is one share in G per interval.
+#[derive(Debug)]
+pub struct GenericMicKey<T: Domain, G: Group = u32> {
+    pub alpha_share: T,
+    pub intervals: Vec<(T, T)>,
+    pub z: Vec<G>,
+    pub s: u128,
+    pub cw: Vec<CompressedCorrectionWord<G>>,
+    pub cw_leaf: Vec<G>,
+}
+
+/// Multiple interval containment key on 32 bits.
+pub type MicKey = GenericMicKey<u32>;
+
+impl<T: Domain, G: Group> GenericMicKey<T, G> {
+    /// Keys for the indicators of x in each [lower, upper] interval, evaluated on x + alpha
+    /// where alpha is a random mask shared between the keys.
+    pub fn generate_keypair(prg: &mut impl Prg, intervals: &[(T, T)]) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, intervals, &mut rand::thread_rng())
+    }
+
+    /// Same as `generate_keypair`, with all the randomness drawn from `rng`.
+    pub fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        intervals: &[(T, T)],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        assert!(intervals.iter().all(|&(lower, upper)| lower <= upper));
+
+        // Random mask of the input.
+        let alpha: T = random_domain_element(rng);
+
+        // Initialize seeds.
+        let s_a: u128 = rng.gen();
+        let s_b: u128 = rng.gen();
+
+        // Same DIF key as `GenericIntervalKey`, shared by all the intervals.
+        let gamma = wrapping_sub_domain(alpha, T::from_u64(2));
+        let (cw, cw_leaf) = generate_cw_from_seeds(prg, gamma, G::one(), s_a, s_b);
+
+        // Secret-share alpha and the correction terms between Alice and Bob.
+        let mask: T = random_domain_element(rng);
+        let mut z_a = Vec::with_capacity(intervals.len());
+        let mut z_b = Vec::with_capacity(intervals.len());
+        for &(lower, upper) in intervals {
+            let z: G = correction_term(alpha, lower, upper);
+            let z_mask = G::from_block(rng.gen());
+            z_a.push(z.sub(z_mask));
+            z_b.push(z_mask);
+        }
+
+        // Return a key pair.
+        (
+            GenericMicKey {
+                alpha_share: wrapping_sub_domain(alpha, mask),
+                intervals: intervals.to_vec(),
+                z: z_a,
+                s: s_a,
+                cw: cw.clone(),
+                cw_leaf: cw_leaf.clone(),
+            },
+            GenericMicKey {
+                alpha_share: mask,
+                intervals: intervals.to_vec(),
+                z: z_b,
+                s: s_b,
+                cw,
+                cw_leaf,
+            },
+        )
+    }
+
+    /// Shares of 1[lower_i <= x <= upper_i] for each interval, where x + alpha is the public input.
+    pub fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> Vec<G> {
+        assert!((party_id == 0u8) || (party_id == 1u8));
+
+        // Two points per interval, evaluated in a single walk.
+        let mut points = Vec::with_capacity(2 * self.intervals.len());
+        for &(lower, upper) in self.intervals.iter() {
+            let (x_p, x_q1) = shifted_inputs(x, lower, upper);
+            points.push(x_p);
+            points.push(x_q1);
+        }
+        let shares = eval_points(prg, party_id, self.s, &self.cw, &self.cw_leaf, &points);
+
+        self.intervals
+            .iter()
+            .enumerate()
+            .map(|(i, &(lower, upper))| {
+                combine_shares(
+                    party_id,
+                    x,
+                    lower,
+                    upper,
+                    self.z[i],
+                    shares[2 * i],
+                    shares[2 * i + 1],
+                )
+            })
+            .collect()
+    }
+}
+
+// Public term of the gate, known to the dealer only since it depends on alpha.
+fn correction_term<T: Domain, G: Group>(alpha: T, lower: T, upper: T) -> G {
+    let one = T::from_u64(1);
+    let alpha_p = wrapping_add_domain(lower, alpha);
+    let alpha_q = wrapping_add_domain(upper, alpha);
+    let upper_1 = wrapping_add_domain(upper, one);
+    let alpha_q1 = wrapping_add_domain(upper_1, alpha);
+    let max = T::from_u64(u64::MAX);
+    let mut z = G::zero();
+    if alpha_p > alpha_q {
+        z = z.add(G::one());
+    }
+    if alpha_p > lower {
+        z = z.sub(G::one());
+    }
+    if alpha_q1 > upper_1 {
+        z = z.add(G::one());
+    }
+    if alpha_q == max {
+        z = z.add(G::one());
+    }
+    z
+}
+
+// Masked inputs x - 1 - lower and x - 1 - (upper + 1) for the DIF key.
+fn shifted_inputs<T: Domain>(x: T, lower: T, upper: T) -> (T, T) {
+    let one = T::from_u64(1);
+    let upper_1 = wrapping_add_domain(upper, one);
+    let x_1 = wrapping_sub_domain(x, one);
+    (
+        wrapping_sub_domain(x_1, lower),
+        wrapping_sub_domain(x_1, upper_1),
+    )
+}
+
+// Share of the indicator from the DIF shares on both shifted inputs.
+fn combine_shares<T: Domain, G: Group>(
+    party_id: u8,
+    x: T,
+    lower: T,
+    upper: T,
+    z: G,
+    s_p: G,
+    s_q1: G,
+) -> G {
+    let upper_1 = wrapping_add_domain(upper, T::from_u64(1));
+    let mut out = z.add(s_q1).sub(s_p);
+    if party_id == 1 {
+        if x > lower {
+            out = out.add(G::one());
+        }
+        if x > upper_1 {
+            out = out.sub(G::one());
+        }
+    }
+    out
+}
+
+//
+// Serialization functions
+//
+
+fn write_key_to_array<T: Domain, G: Group>(key: &GenericIntervalKey<T, G>, array: &mut [u8]) {
+    let n = T::N;
+    write_domain_element(key.alpha_share, &mut array[0..n]);
+    write_domain_element(key.lower, &mut array[n..2 * n]);
+    write_domain_element(key.upper, &mut array[2 * n..3 * n]);
+    let j = 3 * n;
+    key.z.write_le_bytes(&mut array[j..j + G::BYTES]);
+    write_tree_to_array(key.s, &key.cw, &key.cw_leaf, &mut array[j + G::BYTES..]);
+}
+
+fn read_key_from_array<T: Domain, G: Group>(array: &[u8]) -> GenericIntervalKey<T, G> {
+    let n = T::N;
+    let alpha_share = read_domain_element(&array[0..n]);
+    let lower = read_domain_element(&array[n..2 * n]);
+    let upper = read_domain_element(&array[2 * n..3 * n]);
+    let j = 3 * n;
+    let z = G::read_le_bytes(&array[j..j + G::BYTES]);
+    let (s, cw, cw_leaf) = read_tree_from_array(&array[j + G::BYTES..], n * 8);
+
+    GenericIntervalKey {
+        alpha_share,
+        lower,
+        upper,
+        z,
+        s,
+        cw,
+        cw_leaf,
+    }
+}
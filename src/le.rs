@@ -2,200 +2,219 @@
 //! Comparison keys tailored for AriaNN
 //!
 
-use rand::Rng;
-use std::convert::TryInto;
-use std::fmt;
+use rand::{CryptoRng, Rng, RngCore};
+use std::marker::PhantomData;
 
 use std::slice;
 
 use crate::fss::dif::{
-    decompress_word, generate_cw_from_seeds, h, xor_2_words, CompressedCorrectionWord,
+    eval_path, eval_paths, eval_range_into, generate_cw_from_seeds_with, read_tree_from_array,
+    tree_len, write_tree_to_array, Comparison, CompressedCorrectionWord, Ge, Gt, Le, Lt,
 };
 use crate::stream::{FSSKey, Prg, RawKey};
-use crate::utils::{bit_decomposition_u32, compute_out};
-use crate::{L, N};
+use crate::utils::{
+    random_domain_element, read_domain_element, wrapping_add_domain, wrapping_sub_domain,
+    write_domain_element, Domain, Group,
+};
 
-pub struct LeKey {
-    pub alpha_share: u32,
+/// Comparison key for inputs in T, with one tree level per bit of T.
+/// The output is shared in G, and C is the comparison with alpha, e.g. x <= alpha.
+#[derive(Debug)]
+pub struct GenericComparisonKey<T: Domain, G: Group = u32, C = Le> {
+    pub alpha_share: T,
     pub s: u128,
-    pub cw: [CompressedCorrectionWord; N * 8],
-    pub cw_leaf: [u32; N * 8 + 1],
+    pub cw: Vec<CompressedCorrectionWord<G>>,
+    pub cw_leaf: Vec<G>,
+    comparison: PhantomData<C>,
 }
 
-impl fmt::Debug for LeKey {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let v = &self.cw_leaf[..].to_vec();
-        f.debug_struct("LeKey")
-            .field("alpha_share", &self.alpha_share)
-            .field("s", &self.s)
-            .field("cw", &self.cw)
-            .field("cw_leaf", &v)
-            .finish()
-    }
-}
+/// Comparison key for x <= alpha.
+pub type GenericLeKey<T, G = u32> = GenericComparisonKey<T, G, Le>;
+
+/// Comparison key for x < alpha.
+pub type GenericLtKey<T, G = u32> = GenericComparisonKey<T, G, Lt>;
+
+/// Comparison key for x > alpha.
+pub type GenericGtKey<T, G = u32> = GenericComparisonKey<T, G, Gt>;
+
+/// Comparison key for x >= alpha.
+pub type GenericGeKey<T, G = u32> = GenericComparisonKey<T, G, Ge>;
+
+/// Comparison key on 32 bits, used by the FFI.
+pub type LeKey = GenericLeKey<u32>;
+
+/// Comparison key on 32 bits with shares in Z/2^64Z, used by the FFI.
+pub type LeKey64 = GenericLeKey<u32, u64>;
+
+/// Comparison key on 32 bits with XOR shares, used by the FFI.
+pub type LeKeyXor = GenericLeKey<u32, bool>;
 
-const CW_LEN: usize = 24;
+/// Strict comparison keys on 32 bits, used by the FFI.
+pub type LtKey = GenericLtKey<u32>;
+pub type GtKey = GenericGtKey<u32>;
+pub type GeKey = GenericGeKey<u32>;
 
-impl RawKey for LeKey {
-    // 4 + 16 + 24 * (4 * 8) + 4 * (4 * 8 + 1)
-    const KEY_LEN: usize = 920;
+impl<T: Domain, G: Group, C> RawKey for GenericComparisonKey<T, G, C> {
+    // N + 16 + 24 * (N * 8) + 4 * (N * 8 + 1), i.e. 920 bytes for u32
+    const KEY_LEN: usize = T::N + tree_len::<G>(T::N * 8);
 
     unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
-        // Cast the output line to a raw pointer.
-        let out_ptr: *mut [u8; Self::KEY_LEN] =
-            slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN).as_mut_ptr()
-                as *mut [u8; LeKey::KEY_LEN];
-        // Get a mutable reference.
-        let out_ref: &mut [u8; Self::KEY_LEN] = &mut *out_ptr;
+        // Get a mutable reference to the output line.
+        let out_ref: &mut [u8] = slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN);
         // Write the key.
         write_key_to_array(self, out_ref);
     }
 
     unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
-        let key_ptr = slice::from_raw_parts(key_pointer, Self::KEY_LEN).as_ptr()
-            as *const [u8; Self::KEY_LEN];
-        read_key_from_array(&*key_ptr)
+        read_key_from_array(slice::from_raw_parts(key_pointer, Self::KEY_LEN))
     }
 }
 
-impl FSSKey for LeKey {
-    fn generate_keypair(prg: &mut impl Prg) -> (Self, Self) {
-        // Thread randomness for parallelization.
-        let mut rng = rand::thread_rng();
+impl<T: Domain, G: Group, C: Comparison> FSSKey for GenericComparisonKey<T, G, C> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
         // TODO: we can replace this randomness by AES-generated randomness and reduce the key size.
-        // Random point on which we will check equality.
-        let alpha: u32 = rng.gen();
+        // Random point on which we will check equality, secret-shared with a random mask.
+        let alpha: T = random_domain_element(rng);
+        let mask: T = random_domain_element(rng);
+        Self::generate_keypair_from_alpha(prg, alpha, mask, rng)
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        // The final sum is a share of the comparison of x with alpha in G
+        eval_path(prg, party_id, self.s, &self.cw, &self.cw_leaf, x)
+    }
+
+    fn eval_batch(keys: &[Self], prg: &mut impl Prg, party_id: u8, xs: &[T], out: &mut [G]) {
+        let trees: Vec<_> = keys
+            .iter()
+            .map(|key| (key.s, &key.cw[..], &key.cw_leaf[..]))
+            .collect();
+        eval_paths(prg, party_id, &trees, xs, out)
+    }
+}
+
+impl<T: Domain, G: Group, C: Comparison> GenericComparisonKey<T, G, C> {
+    /// Keys for a given alpha, e.g. a mask preprocessed by another protocol.
+    /// Bob's share of alpha is `mask` and Alice's share is alpha - mask.
+    /// Only the seeds are drawn from `rng`.
+    pub fn generate_keypair_from_alpha(
+        prg: &mut impl Prg,
+        alpha: T,
+        mask: T,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
         // Initialize seeds.
         let s_a: u128 = rng.gen();
         let s_b: u128 = rng.gen();
-        let (cw, cw_leaf) = generate_cw_from_seeds(prg, alpha, s_a, s_b);
-        // Secret-share alpha and split the keys between Alice and Bob.
-        let mask: u32 = rng.gen();
-        // Return a key pair.
+        let (below, equal, above) = C::leaves(G::one());
+        let (cw, cw_leaf) = generate_cw_from_seeds_with(prg, alpha, below, equal, above, s_a, s_b);
+        // Return a key pair, with alpha secret-shared between Alice and Bob.
         (
-            LeKey {
-                alpha_share: alpha.wrapping_sub(mask),
+            GenericComparisonKey {
+                alpha_share: wrapping_sub_domain(alpha, mask),
                 s: s_a,
-                cw,
-                cw_leaf,
+                cw: cw.clone(),
+                cw_leaf: cw_leaf.clone(),
+                comparison: PhantomData,
             },
-            LeKey {
+            GenericComparisonKey {
                 alpha_share: mask,
                 s: s_b,
                 cw,
                 cw_leaf,
+                comparison: PhantomData,
             },
         )
     }
+}
 
-    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32 {
-        assert!((party_id == 0u8) || (party_id == 1u8));
-        let mut t_i: u8 = party_id;
-        let mut s_i: u128 = self.s;
-        let mut u_i;
-        let mut z_i;
-        let mut out = 0u32;
-        let x_bits: Vec<u8> = bit_decomposition_u32(x);
-        for i in 0..(N * 8) {
-            let mut w = h(prg, s_i);
-            if t_i == 1 {
-                w = xor_2_words(&w, &decompress_word(&self.cw[i]))
-            }
-            if x_bits[i] == 0 {
-                z_i = w.z_l;
-                u_i = w.u_l;
-                s_i = w.s_l;
-                t_i = w.t_l;
-            } else {
-                z_i = w.z_r;
-                u_i = w.u_r;
-                s_i = w.s_r;
-                t_i = w.t_r;
-            }
-
-            // Mask and sum in Z/2^32Z
-            let out_i = compute_out(z_i, self.cw_leaf[i], u_i, party_id);
-            out = out.wrapping_add(out_i);
-        }
-        let out_n = compute_out(s_i as u32, self.cw_leaf[N * 8], t_i, party_id);
-        // The final sum is a share of (x <= alpha) in Z/2^32Z
-        out.wrapping_add(out_n)
+impl<T: Domain, G: Group, C> GenericComparisonKey<T, G, C> {
+    /// Shares on the masked inputs x = start, ..., start + out.len() - 1, without wrapping around.
+    pub fn eval_range<P: Prg + Clone + Sync>(
+        &self,
+        prg: &P,
+        party_id: u8,
+        start: T,
+        out: &mut [G],
+    ) {
+        eval_range_into(prg, party_id, self.s, &self.cw, &self.cw_leaf, start, out);
     }
 }
 
-///
-/// Serialization functions
-///
-
-fn write_key_to_array(key: &LeKey, array: &mut [u8; LeKey::KEY_LEN]) {
-    array[0..N].copy_from_slice(&key.alpha_share.to_le_bytes());
-    array[N..(N + L)].copy_from_slice(&key.s.to_le_bytes());
-    for i in 0..(N * 8) {
-        // Start index for the control word
-        let mut j = N + L + i * CW_LEN;
-        let cw = key.cw[i];
-
-        // Copy seeds first (u128)
-        array[j..j + L].copy_from_slice(&cw.s.to_le_bytes());
-        array[j + L..j + L + N].copy_from_slice(&cw.z.to_le_bytes());
-
-        // Copy control bits at the end (u8)
-        j = j + L + N;
-        array[j] = cw.t_l;
-        array[j + 1] = cw.t_r;
-        array[j + 2] = cw.u_l;
-        array[j + 3] = cw.u_r;
+/// Comparison key for two's complement inputs in T, i.e. x <= alpha in signed order.
+/// Flipping the most significant bit maps the signed order to the unsigned one,
+/// so the inner key compares x ^ msb <= alpha ^ msb. The raw layout is the same as `GenericLeKey`,
+/// with shares of alpha itself.
+#[derive(Debug)]
+pub struct GenericSignedLeKey<T: Domain, G: Group = u32>(pub GenericLeKey<T, G>);
+
+/// Signed comparison key on 32 bits, used by the FFI.
+pub type SignedLeKey = GenericSignedLeKey<u32>;
+
+// Most significant bit of T, as an element of T.
+fn msb<T: Domain>() -> T {
+    T::from_u64(1 << (T::N * 8 - 1))
+}
+
+impl<T: Domain, G: Group> RawKey for GenericSignedLeKey<T, G> {
+    const KEY_LEN: usize = GenericLeKey::<T, G>::KEY_LEN;
+
+    unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
+        self.0.to_raw_line(key_pointer)
     }
-    for i in 0..(N * 8 + 1) {
-        let j = N + L + N * 8 * CW_LEN + i * N;
-        array[j..j + N].copy_from_slice(&key.cw_leaf[i].to_le_bytes());
+
+    unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
+        GenericSignedLeKey(GenericLeKey::from_raw_line(key_pointer))
     }
 }
 
-fn read_key_from_array(array: &[u8; LeKey::KEY_LEN]) -> LeKey {
-    let alpha_share = u32::from_le_bytes(array[0..N].try_into().unwrap());
-    let s = u128::from_le_bytes(array[N..(N + L)].try_into().unwrap());
-
-    let mut cw = [CompressedCorrectionWord {
-        z: 0,
-        s: 0,
-        t_l: 0,
-        t_r: 0,
-        u_l: 0,
-        u_r: 0,
-    }; N * 8];
-    let mut cw_leaf = [0u32; N * 8 + 1];
-
-    for i in 0..(N * 8) {
-        let mut j = N + L + i * CW_LEN;
-
-        let s = u128::from_le_bytes(array[j..j + L].try_into().unwrap());
-        let z = u32::from_le_bytes(array[j + L..j + L + N].try_into().unwrap());
-
-        j = j + L + N;
-        let t_l = array[j];
-        let t_r = array[j + 1];
-        let u_l = array[j + 2];
-        let u_r = array[j + 3];
-
-        cw[i] = CompressedCorrectionWord {
-            s,
-            z,
-            t_l,
-            u_l,
-            t_r,
-            u_r,
-        }
+impl<T: Domain, G: Group> FSSKey for GenericSignedLeKey<T, G> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        // The inner alpha is a random point, alpha ^ msb is too.
+        let (mut key_a, key_b) = GenericLeKey::generate_keypair_from_rng(prg, rng);
+        // Alice's share now sums to alpha ^ msb, the point compared in signed order.
+        key_a.alpha_share = wrapping_add_domain(key_a.alpha_share, msb());
+        (GenericSignedLeKey(key_a), GenericSignedLeKey(key_b))
     }
-    for i in 0..(N * 8 + 1) {
-        let j = N + L + N * 8 * CW_LEN + i * N;
-        cw_leaf[i] = u32::from_le_bytes(array[j..j + N].try_into().unwrap());
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        self.0.eval(prg, party_id, wrapping_add_domain(x, msb()))
     }
+}
+
+//
+// Serialization functions
+//
+
+fn write_key_to_array<T: Domain, G: Group, C>(
+    key: &GenericComparisonKey<T, G, C>,
+    array: &mut [u8],
+) {
+    write_domain_element(key.alpha_share, &mut array[0..T::N]);
+    write_tree_to_array(key.s, &key.cw, &key.cw_leaf, &mut array[T::N..]);
+}
+
+fn read_key_from_array<T: Domain, G: Group, C>(array: &[u8]) -> GenericComparisonKey<T, G, C> {
+    let alpha_share = read_domain_element(&array[0..T::N]);
+    let (s, cw, cw_leaf) = read_tree_from_array(&array[T::N..], T::N * 8);
 
-    LeKey {
+    GenericComparisonKey {
         alpha_share,
         s,
         cw,
         cw_leaf,
+        comparison: PhantomData,
     }
 }
@@ -4,19 +4,62 @@ use rayon::prelude::*;
 
 pub mod eq;
 pub mod fss;
+pub mod interval;
 pub mod le;
+pub mod lut;
+pub mod maxpool;
+pub mod msb;
+pub mod relu;
 pub mod stream;
+pub mod trunc;
 pub mod utils;
 
+use eq::{EqKey, EqKey64, EqKeyXor};
+use interval::IntervalKey;
+use le::{GeKey, GtKey, LeKey, LeKey64, LeKeyXor, LtKey, SignedLeKey};
+use maxpool::{CallbackChannel, ExchangeCallback, MaxpoolMaterial};
+use msb::{BitDecompositionKey, BitDecompositionKeyXor, MsbKey, MsbKeyXor};
+use relu::ReluKey;
+use stream::{Prg, PrgId, RawKey, PRG_HEADER_LEN};
+use trunc::TruncKey;
+use utils::{read_aes_key_from_raw_line, session_aes_keys, write_aes_key_to_raw_line, Mmo};
+
 // Byte precision and security.
+/// Bytes of the inputs read by the FFI: every op is on 32 bits. Keys on other domains,
+/// e.g. `GenericEqKey<u8>` or `GenericLeKey<u64>`, are only available from Rust.
 pub const N: usize = 4;
 pub const L: usize = 16;
 
 fn build_params(op_id: usize) -> (usize, usize, usize) {
     let (keylen, n_aes_keys) = match op_id {
         // 1 => (1205, 4),
-        1 => (920, 3),
-        _ => (621, 2),
+        1 => (LeKey::KEY_LEN, 3),
+        // Same keys with shares in Z/2^64Z
+        2 => (EqKey64::KEY_LEN, 2),
+        3 => (LeKey64::KEY_LEN, 3),
+        // Same keys with XOR shares
+        4 => (EqKeyXor::KEY_LEN, 2),
+        5 => (LeKeyXor::KEY_LEN, 3),
+        // Interval containment, with the bounds passed as parameters
+        6 => (IntervalKey::KEY_LEN, 3),
+        7 => (ReluKey::KEY_LEN, 3),
+        // Comparison in two's complement order
+        8 => (SignedLeKey::KEY_LEN, 3),
+        // Other comparison directions: x < alpha, x > alpha, x >= alpha
+        9 => (LtKey::KEY_LEN, 3),
+        10 => (GtKey::KEY_LEN, 3),
+        11 => (GeKey::KEY_LEN, 3),
+        // Truncation by f bits given as parameter, logical and arithmetic shifts
+        12 | 13 => (TruncKey::KEY_LEN, 3),
+        // MSB extraction and bit decomposition, in Z/2^32Z and with XOR shares
+        14 => (MsbKey::KEY_LEN, 3),
+        15 => (MsbKeyXor::KEY_LEN, 3),
+        16 => (BitDecompositionKey::KEY_LEN, 3),
+        17 => (BitDecompositionKeyXor::KEY_LEN, 3),
+        // Equality and comparison with alpha and its split given for each key
+        18 => (EqKey::KEY_LEN, 2),
+        19 => (LeKey::KEY_LEN, 3),
+        _ => (EqKey::KEY_LEN, 2),
     };
 
     // TODO: small inputs
@@ -25,6 +68,66 @@ fn build_params(op_id: usize) -> (usize, usize, usize) {
     (n_aes_keys, keylen, n_aes_streams)
 }
 
+// Number of results written for each input.
+fn n_outputs(op_id: usize) -> usize {
+    match op_id {
+        // One share per bit
+        16 | 17 => N * 8,
+        _ => 1,
+    }
+}
+
+/// Number of public keys of the Prg of an op, i.e. its expansion factor: AES keys for Mmo,
+/// or keys that the ChaCha Prgs all chain into their public key.
+#[no_mangle]
+pub extern "C" fn n_aes_keys(op_id: usize) -> usize {
+    build_params(op_id).0
+}
+
+// The public AES keys read from raw lines of 16 bytes, or the default keys 0, 1, 2, ...
+unsafe fn read_aes_keys(op_id: usize, aes_keys_pointer: *const u8, n_aes_keys: usize) -> Vec<u128> {
+    let expected = build_params(op_id).0;
+    if aes_keys_pointer.is_null() {
+        assert_eq!(n_aes_keys, 0);
+        return (0..expected).map(|i| i as u128).collect();
+    }
+    assert_eq!(
+        n_aes_keys, expected,
+        "op_id {} expects {} AES keys",
+        op_id, expected
+    );
+    (0..n_aes_keys)
+        .map(|i| read_aes_key_from_raw_line(aes_keys_pointer.add(16 * i)))
+        .collect()
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Writes `n_aes_keys` public AES keys of 16 bytes, derived from a 16 bytes session id.
+/// Keygen and eval must be given the same keys.
+#[no_mangle]
+pub unsafe extern "C" fn derive_aes_keys(
+    session_id_pointer: *const u8,
+    aes_keys_pointer: *mut u8,
+    n_aes_keys: usize,
+) {
+    assert!(!session_id_pointer.is_null());
+    assert!(!aes_keys_pointer.is_null());
+    let session_id = read_aes_key_from_raw_line(session_id_pointer);
+    for (i, key) in session_aes_keys(session_id, n_aes_keys).iter().enumerate() {
+        write_aes_key_to_raw_line(*key, aes_keys_pointer.add(16 * i));
+    }
+}
+
+// Number of parameters for each key, or 0 if the parameters are shared by all the keys.
+fn n_params_per_key(op_id: usize) -> usize {
+    match op_id {
+        // [alpha, mask]
+        18 | 19 => 2,
+        _ => 0,
+    }
+}
+
 /// # Safety
 /// Declare function to be used within C
 #[no_mangle]
@@ -34,18 +137,157 @@ pub unsafe extern "C" fn keygen(
     n_values: usize,
     n_threads: usize,
     op_id: usize,
+) {
+    keygen_with_params(
+        keys_a_pointer,
+        keys_b_pointer,
+        n_values,
+        n_threads,
+        op_id,
+        std::ptr::null(),
+        0,
+    );
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Same as `keygen`, for keys with public parameters (e.g. interval bounds)
+/// or with parameters for each key, one after the other (e.g. [alpha_0, mask_0, alpha_1, ...])
+#[no_mangle]
+pub unsafe extern "C" fn keygen_with_params(
+    keys_a_pointer: *mut u8,
+    keys_b_pointer: *mut u8,
+    n_values: usize,
+    n_threads: usize,
+    op_id: usize,
+    params_pointer: *const u64,
+    n_params: usize,
+) {
+    keygen_lines(
+        keys_a_pointer,
+        keys_b_pointer,
+        n_values,
+        n_threads,
+        op_id,
+        params_pointer,
+        n_params,
+        std::ptr::null(),
+        0,
+        PrgId::Mmo,
+        false,
+    );
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Same as `keygen_with_params`, with the public AES keys of the Prg as `n_aes_keys` lines
+/// of 16 bytes (e.g. from `derive_aes_keys`), or the default keys if the pointer is null.
+/// Each key line starts with a header of `PRG_HEADER_LEN` bytes recording the Prg and
+/// a fingerprint of the AES keys, so the lines are `PRG_HEADER_LEN` bytes longer
+/// than with `keygen`.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub unsafe extern "C" fn keygen_with_aes_keys(
+    keys_a_pointer: *mut u8,
+    keys_b_pointer: *mut u8,
+    n_values: usize,
+    n_threads: usize,
+    op_id: usize,
+    params_pointer: *const u64,
+    n_params: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+) {
+    keygen_lines(
+        keys_a_pointer,
+        keys_b_pointer,
+        n_values,
+        n_threads,
+        op_id,
+        params_pointer,
+        n_params,
+        aes_keys_pointer,
+        n_aes_keys,
+        PrgId::Mmo,
+        true,
+    );
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Same as `keygen_with_aes_keys`, with the Prg given by `prg_id`
+/// (0: Mmo, 1: ChaCha8, 2: ChaCha12, 3: ChaCha20) and its public keys.
+/// The key lines have the same header as with `keygen_with_aes_keys`.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub unsafe extern "C" fn keygen_with_prg(
+    keys_a_pointer: *mut u8,
+    keys_b_pointer: *mut u8,
+    n_values: usize,
+    n_threads: usize,
+    op_id: usize,
+    params_pointer: *const u64,
+    n_params: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+    prg_id: usize,
+) {
+    keygen_lines(
+        keys_a_pointer,
+        keys_b_pointer,
+        n_values,
+        n_threads,
+        op_id,
+        params_pointer,
+        n_params,
+        aes_keys_pointer,
+        n_aes_keys,
+        PrgId::from_id(prg_id),
+        true,
+    );
+}
+
+// Key lines of `keylen` bytes, or with the header of the Prg and its public keys first.
+#[allow(clippy::too_many_arguments)]
+unsafe fn keygen_lines(
+    keys_a_pointer: *mut u8,
+    keys_b_pointer: *mut u8,
+    n_values: usize,
+    n_threads: usize,
+    op_id: usize,
+    params_pointer: *const u64,
+    n_params: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+    prg_id: PrgId,
+    with_header: bool,
 ) {
     assert!(!keys_a_pointer.is_null());
     assert!(!keys_b_pointer.is_null());
 
-    let (n_aes_keys, keylen, n_aes_streams) = build_params(op_id);
+    let params: &[u64] = if n_params == 0 {
+        &[]
+    } else {
+        assert!(!params_pointer.is_null());
+        std::slice::from_raw_parts(params_pointer, n_params)
+    };
 
-    // Harcoded AES-128 keys for Mmo
-    let mut aes_keys = Vec::new();
-    for i in 0..n_aes_keys {
-        aes_keys.push(i as u128);
+    let (_, keylen, n_aes_streams) = build_params(op_id);
+    let line_len = keylen + if with_header { PRG_HEADER_LEN } else { 0 };
+    let n_params_per_key = n_params_per_key(op_id);
+    if n_params_per_key > 0 {
+        assert_eq!(
+            params.len(),
+            n_params_per_key * n_values,
+            "op_id {} expects {} parameters per key",
+            op_id,
+            n_params_per_key
+        );
     }
 
+    // Public keys of the Prg, e.g. AES-128 keys for Mmo
+    let aes_keys = read_aes_keys(op_id, aes_keys_pointer, n_aes_keys);
+
     let mut key_stream_args = vec![];
     let mut line_counter = 0;
     let default_length = n_values / n_aes_streams;
@@ -65,16 +307,22 @@ pub unsafe extern "C" fn keygen(
             key_stream_args.push((
                 stream_id,
                 stream_length,
-                keys_a_pointer.add(keylen * line_counter) as usize,
-                keys_b_pointer.add(keylen * line_counter) as usize,
+                keys_a_pointer.add(line_len * line_counter) as usize,
+                keys_b_pointer.add(line_len * line_counter) as usize,
+                line_counter,
             ));
             line_counter += stream_length;
         }
     }
 
     // Each thread will repeatedly execute this closure in parallel
-    let create_keypair = |key_stream_arg: &(usize, usize, usize, usize)| {
-        let (stream_id, stream_length, key_a_pointer, keys_b_pointer) = *key_stream_arg;
+    let create_keypair = |key_stream_arg: &(usize, usize, usize, usize, usize)| {
+        let (stream_id, stream_length, key_a_pointer, keys_b_pointer, first_line) = *key_stream_arg;
+        // Each stream only sees the parameters of its own keys.
+        let stream_params = match n_params_per_key {
+            0 => params,
+            n => &params[n * first_line..n * (first_line + stream_length)],
+        };
         stream::generate_key_stream(
             &aes_keys,
             stream_id,
@@ -82,6 +330,9 @@ pub unsafe extern "C" fn keygen(
             key_a_pointer,
             keys_b_pointer,
             op_id,
+            stream_params,
+            prg_id,
+            with_header,
         );
     };
 
@@ -103,18 +354,112 @@ pub unsafe extern "C" fn eval(
     n_values: usize,
     n_threads: usize,
     op_id: usize,
+) {
+    eval_lines(
+        party_id,
+        xs_pointer,
+        keys_pointer,
+        results_pointer,
+        n_values,
+        n_threads,
+        op_id,
+        std::ptr::null(),
+        0,
+        PrgId::Mmo,
+        false,
+    );
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Same as `eval`, for keys from `keygen_with_aes_keys` with the same AES keys.
+/// Panics if the header of a key records other AES keys.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub unsafe extern "C" fn eval_with_aes_keys(
+    party_id: usize,
+    xs_pointer: *const u8,
+    keys_pointer: *const u8,
+    results_pointer: *mut i64,
+    n_values: usize,
+    n_threads: usize,
+    op_id: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+) {
+    eval_lines(
+        party_id,
+        xs_pointer,
+        keys_pointer,
+        results_pointer,
+        n_values,
+        n_threads,
+        op_id,
+        aes_keys_pointer,
+        n_aes_keys,
+        PrgId::Mmo,
+        true,
+    );
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Same as `eval_with_aes_keys`, for keys from `keygen_with_prg` with the same Prg.
+/// Panics if the header of a key records another Prg or other public keys.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub unsafe extern "C" fn eval_with_prg(
+    party_id: usize,
+    xs_pointer: *const u8,
+    keys_pointer: *const u8,
+    results_pointer: *mut i64,
+    n_values: usize,
+    n_threads: usize,
+    op_id: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+    prg_id: usize,
+) {
+    eval_lines(
+        party_id,
+        xs_pointer,
+        keys_pointer,
+        results_pointer,
+        n_values,
+        n_threads,
+        op_id,
+        aes_keys_pointer,
+        n_aes_keys,
+        PrgId::from_id(prg_id),
+        true,
+    );
+}
+
+// Key lines of `keylen` bytes, or with the header of the Prg and its public keys first.
+#[allow(clippy::too_many_arguments)]
+unsafe fn eval_lines(
+    party_id: usize,
+    xs_pointer: *const u8,
+    keys_pointer: *const u8,
+    results_pointer: *mut i64,
+    n_values: usize,
+    n_threads: usize,
+    op_id: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+    prg_id: PrgId,
+    with_header: bool,
 ) {
     assert!(!xs_pointer.is_null());
     assert!(!keys_pointer.is_null());
     assert!(!results_pointer.is_null());
 
-    let (n_aes_keys, keylen, n_aes_streams) = build_params(op_id);
+    let (_, keylen, n_aes_streams) = build_params(op_id);
+    let n_outputs = n_outputs(op_id);
+    let line_len = keylen + if with_header { PRG_HEADER_LEN } else { 0 };
 
-    // Harcoded AES-128 keys for Mmo
-    let mut aes_keys = Vec::new();
-    for i in 0..n_aes_keys {
-        aes_keys.push(i as u128);
-    }
+    // Public keys of the Prg, e.g. AES-128 keys for Mmo
+    let aes_keys = read_aes_keys(op_id, aes_keys_pointer, n_aes_keys);
 
     let mut key_stream_args = vec![];
     let mut line_counter = 0;
@@ -136,8 +481,8 @@ pub unsafe extern "C" fn eval(
                 stream_id,
                 stream_length,
                 xs_pointer.add(N * line_counter) as usize,
-                keys_pointer.add(keylen * line_counter) as usize,
-                results_pointer.add(line_counter) as usize,
+                keys_pointer.add(line_len * line_counter) as usize,
+                results_pointer.add(n_outputs * line_counter) as usize,
             ));
             line_counter += stream_length;
         }
@@ -155,6 +500,8 @@ pub unsafe extern "C" fn eval(
             key_pointer,
             result_pointer,
             op_id,
+            prg_id,
+            with_header,
         );
     };
 
@@ -164,3 +511,77 @@ pub unsafe extern "C" fn eval(
         .build_global();
     key_stream_args.par_iter().for_each(eval_key);
 }
+
+// Maxpool compares the values with the MSB keys of op_id 14, with the same AES keys.
+const MAXPOOL_OP_ID: usize = 14;
+
+/// Bytes of the maxpool material of one party for a window of k values.
+#[no_mangle]
+pub extern "C" fn maxpool_material_len(k: usize) -> usize {
+    MaxpoolMaterial::raw_len(k)
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Writes the maxpool material of each party for `n_windows` windows of k values,
+/// as lines of `maxpool_material_len(k)` bytes. The AES keys are the ones of op_id 14,
+/// or the default keys if the pointer is null.
+#[no_mangle]
+pub unsafe extern "C" fn maxpool_keygen(
+    materials_a_pointer: *mut u8,
+    materials_b_pointer: *mut u8,
+    n_windows: usize,
+    k: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+) {
+    assert!(!materials_a_pointer.is_null());
+    assert!(!materials_b_pointer.is_null());
+
+    let line_len = MaxpoolMaterial::raw_len(k);
+    let aes_keys = read_aes_keys(MAXPOOL_OP_ID, aes_keys_pointer, n_aes_keys);
+    let mut prg = Mmo::<3>::from_vec(&aes_keys);
+    for i in 0..n_windows {
+        let (material_a, material_b) = MaxpoolMaterial::generate_pair(&mut prg, k);
+        material_a.to_raw_line(materials_a_pointer.add(line_len * i));
+        material_b.to_raw_line(materials_b_pointer.add(line_len * i));
+    }
+}
+
+/// # Safety
+/// Declare function to be used within C
+/// Writes the shares of the max of `n_windows` windows of k shares in Z/2^32Z, back to back,
+/// and the shares of their one-hot argmax, k per window.
+/// `exchange` opens masked values with the other party, twice per level of the tournament.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub unsafe extern "C" fn maxpool_eval(
+    party_id: usize,
+    xs_pointer: *const u32,
+    materials_pointer: *const u8,
+    max_pointer: *mut u32,
+    argmax_pointer: *mut u32,
+    n_windows: usize,
+    k: usize,
+    aes_keys_pointer: *const u8,
+    n_aes_keys: usize,
+    exchange: ExchangeCallback,
+) {
+    assert!(!xs_pointer.is_null());
+    assert!(!materials_pointer.is_null());
+    assert!(!max_pointer.is_null());
+    assert!(!argmax_pointer.is_null());
+
+    let line_len = MaxpoolMaterial::raw_len(k);
+    let materials: Vec<MaxpoolMaterial> = (0..n_windows)
+        .map(|i| MaxpoolMaterial::from_raw_line(materials_pointer.add(line_len * i), k))
+        .collect();
+    let xs = std::slice::from_raw_parts(xs_pointer, n_windows * k);
+
+    let aes_keys = read_aes_keys(MAXPOOL_OP_ID, aes_keys_pointer, n_aes_keys);
+    let mut prg = Mmo::<3>::from_vec(&aes_keys);
+    let mut channel = CallbackChannel { exchange };
+    let (max, argmax) = maxpool::maxpool(&mut prg, party_id as u8, &mut channel, xs, &materials);
+    std::slice::from_raw_parts_mut(max_pointer, n_windows).copy_from_slice(&max);
+    std::slice::from_raw_parts_mut(argmax_pointer, n_windows * k).copy_from_slice(&argmax);
+}
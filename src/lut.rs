@@ -0,0 +1,162 @@
+//!
+//! Lookup table keys for public tables on small domains, built on DPF full domain evaluation
+//!
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::fss::dpf::{eval_range_into_depth, generate_path_cw_bits};
+use crate::stream::Prg;
+use crate::utils::{bit_decomposition, share_leaf, Domain, Group};
+
+/// Largest supported table, with 2^16 entries.
+pub const MAX_LOG_SIZE: usize = 16;
+
+/// Lookup table key for tables of 2^log_size entries in G, with inputs in T.
+/// The DPF tree has log_size levels and its point is the mask alpha < 2^log_size,
+/// so the shares of 1[y = alpha] on its leaves, dotted with the table rotated by x + alpha,
+/// share T[x].
+#[derive(Debug)]
+pub struct GenericLutKey<T: Domain, G: Group = u32> {
+    pub alpha_share: T,
+    pub log_size: u8,
+    pub s: u128,
+    pub cw: Vec<u128>,
+    pub t_l: Vec<u8>,
+    pub t_r: Vec<u8>,
+    pub cw_leaf: G,
+}
+
+/// Lookup table key for 8-bit inputs, e.g. quantized activations.
+pub type LutKey = GenericLutKey<u8>;
+
+/// Lookup table key for inputs up to 16 bits.
+pub type LutKey16 = GenericLutKey<u16>;
+
+impl<T: Domain, G: Group> GenericLutKey<T, G> {
+    /// Keys for tables of 2^log_size entries, evaluated on x + alpha mod 2^log_size
+    /// where alpha is a random mask shared between the keys.
+    pub fn generate_keypair(prg: &mut impl Prg, log_size: u8) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, log_size, &mut rand::thread_rng())
+    }
+
+    /// Same as `generate_keypair`, with all the randomness drawn from `rng`.
+    pub fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        log_size: u8,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        assert!(log_size as usize <= (T::N * 8).min(MAX_LOG_SIZE));
+        let size_mask = (1u64 << log_size) - 1;
+
+        // Random mask of the input, in the table range.
+        let alpha = rng.gen::<u64>() & size_mask;
+
+        // Initialize seeds.
+        let s_a: u128 = rng.gen();
+        let s_b: u128 = rng.gen();
+
+        // Memory allocation for the correction words, one level per bit of the table index
+        let log_size = log_size as usize;
+        let mut cw = vec![0u128; log_size];
+        let mut t_l = vec![0u8; log_size];
+        let mut t_r = vec![0u8; log_size];
+
+        let alpha_bits = bit_decomposition(T::from_u64(alpha));
+        let (s_a_n, s_b_n, t_b_n) = generate_path_cw_bits(
+            prg,
+            &alpha_bits[T::N * 8 - log_size..],
+            s_a,
+            s_b,
+            &mut cw,
+            &mut t_l,
+            &mut t_r,
+        );
+        let cw_leaf = share_leaf(G::from_block(s_a_n), G::from_block(s_b_n), G::one(), t_b_n);
+        let log_size = log_size as u8;
+
+        // Secret-share alpha modulo the table size.
+        let mask = rng.gen::<u64>() & size_mask;
+
+        // Return a key pair.
+        (
+            GenericLutKey {
+                alpha_share: T::from_u64(alpha.wrapping_sub(mask) & size_mask),
+                log_size,
+                s: s_a,
+                cw: cw.clone(),
+                t_l: t_l.clone(),
+                t_r: t_r.clone(),
+                cw_leaf,
+            },
+            GenericLutKey {
+                alpha_share: T::from_u64(mask),
+                log_size,
+                s: s_b,
+                cw,
+                t_l,
+                t_r,
+                cw_leaf,
+            },
+        )
+    }
+
+    /// Share of table[x], where x + alpha mod 2^log_size is the public input.
+    pub fn eval<P: Prg + Clone + Sync>(&self, prg: &P, party_id: u8, x: T, table: &[G]) -> G {
+        let mut point = vec![G::zero(); 1 << self.log_size];
+        self.eval_with_buffer(prg, party_id, x, table, &mut point)
+    }
+
+    /// Shares of table[xs[i]] with keys[i], all with the same table size.
+    /// The leaves of each key are expanded into a single buffer of 2^log_size shares.
+    pub fn eval_batch<P: Prg + Clone + Sync>(
+        keys: &[Self],
+        prg: &P,
+        party_id: u8,
+        xs: &[T],
+        table: &[G],
+        out: &mut [G],
+    ) {
+        assert_eq!(keys.len(), xs.len());
+        assert_eq!(keys.len(), out.len());
+        let mut point = vec![G::zero(); table.len()];
+        for ((key, &x), res) in keys.iter().zip(xs.iter()).zip(out.iter_mut()) {
+            *res = key.eval_with_buffer(prg, party_id, x, table, &mut point);
+        }
+    }
+
+    /// Same as `eval`, with the leaves expanded into `point`, of length 2^log_size.
+    pub fn eval_with_buffer<P: Prg + Clone + Sync>(
+        &self,
+        prg: &P,
+        party_id: u8,
+        x: T,
+        table: &[G],
+        point: &mut [G],
+    ) -> G {
+        let size = 1usize << self.log_size;
+        assert_eq!(table.len(), size);
+        assert_eq!(point.len(), size);
+
+        // Shares of 1[y = alpha] on the table range.
+        eval_range_into_depth(
+            prg,
+            party_id,
+            self.s,
+            &self.cw,
+            &self.t_l,
+            &self.t_r,
+            self.cw_leaf,
+            0,
+            point,
+        );
+
+        // table[x] = sum of table[x + alpha - y] * 1[y = alpha]
+        let x = x.to_u64() as usize;
+        point
+            .iter()
+            .enumerate()
+            .fold(G::zero(), |acc, (y, &share)| {
+                acc.add(share.mul(table[x.wrapping_sub(y) & (size - 1)]))
+            })
+    }
+}
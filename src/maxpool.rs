@@ -0,0 +1,324 @@
+//!
+//! Maxpool and argmax over windows of secret-shared values, in the style of AriaNN
+//!
+
+use rand::{CryptoRng, Rng, RngCore};
+use std::convert::TryInto;
+use std::slice;
+
+use crate::msb::MsbKey;
+use crate::stream::{FSSKey, Prg, RawKey};
+
+/// Link with the other party, used to open masked values.
+/// `exchange` sends our shares and returns the shares of the other party, in the same order.
+pub trait Channel {
+    fn exchange(&mut self, shares: &[u32]) -> Vec<u32>;
+}
+
+/// Exchange through the C ABI: sends the `n_shares` shares and writes the shares
+/// of the other party to `other_shares`.
+pub type ExchangeCallback =
+    unsafe extern "C" fn(shares: *const u32, other_shares: *mut u32, n_shares: usize);
+
+/// `Channel` backed by an `ExchangeCallback`, e.g. a Python function.
+pub struct CallbackChannel {
+    pub exchange: ExchangeCallback,
+}
+
+impl Channel for CallbackChannel {
+    fn exchange(&mut self, shares: &[u32]) -> Vec<u32> {
+        let mut other = vec![0u32; shares.len()];
+        unsafe { (self.exchange)(shares.as_ptr(), other.as_mut_ptr(), shares.len()) };
+        other
+    }
+}
+
+/// Beaver triple shares in Z/2^32Z, with a * b = c.
+#[derive(Debug, Clone, Copy)]
+pub struct BeaverTriple {
+    pub a: u32,
+    pub b: u32,
+    pub c: u32,
+}
+
+/// Preprocessed material of one party for a window of k values:
+/// k - 1 comparison keys and (k - 1) * (k + 1) Beaver triples for the tournament.
+#[derive(Debug)]
+pub struct MaxpoolMaterial {
+    pub k: usize,
+    pub msb_keys: Vec<MsbKey>,
+    pub triples: Vec<BeaverTriple>,
+}
+
+impl MaxpoolMaterial {
+    /// Material of both parties for a window of k values.
+    pub fn generate_pair(prg: &mut impl Prg, k: usize) -> (Self, Self) {
+        Self::generate_pair_from_rng(prg, k, &mut rand::thread_rng())
+    }
+
+    /// Same as `generate_pair`, with all the randomness drawn from `rng`.
+    pub fn generate_pair_from_rng(
+        prg: &mut impl Prg,
+        k: usize,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        assert!(k > 0);
+        let n_comparisons = k - 1;
+
+        let mut msb_keys_a = Vec::with_capacity(n_comparisons);
+        let mut msb_keys_b = Vec::with_capacity(n_comparisons);
+        for _ in 0..n_comparisons {
+            let (key_a, key_b) = MsbKey::generate_keypair_from_rng(prg, rng);
+            msb_keys_a.push(key_a);
+            msb_keys_b.push(key_b);
+        }
+
+        // One product for the max and k for the argmax at each comparison.
+        let n_triples = n_comparisons * (k + 1);
+        let mut triples_a = Vec::with_capacity(n_triples);
+        let mut triples_b = Vec::with_capacity(n_triples);
+        for _ in 0..n_triples {
+            let (a, b): (u32, u32) = (rng.gen(), rng.gen());
+            let mask: (u32, u32, u32) = rng.gen();
+            triples_a.push(BeaverTriple {
+                a: a.wrapping_sub(mask.0),
+                b: b.wrapping_sub(mask.1),
+                c: a.wrapping_mul(b).wrapping_sub(mask.2),
+            });
+            triples_b.push(BeaverTriple {
+                a: mask.0,
+                b: mask.1,
+                c: mask.2,
+            });
+        }
+
+        (
+            MaxpoolMaterial {
+                k,
+                msb_keys: msb_keys_a,
+                triples: triples_a,
+            },
+            MaxpoolMaterial {
+                k,
+                msb_keys: msb_keys_b,
+                triples: triples_b,
+            },
+        )
+    }
+
+    /// Size of the raw material for a window of k values:
+    /// the MSB keys, then the triples as (a, b, c) in little endian.
+    pub fn raw_len(k: usize) -> usize {
+        assert!(k > 0);
+        (k - 1) * MsbKey::KEY_LEN + (k - 1) * (k + 1) * 12
+    }
+
+    /// # Safety
+    /// Writes `MaxpoolMaterial::raw_len(self.k)` bytes
+    pub unsafe fn to_raw_line(&self, raw_line_pointer: *mut u8) {
+        let keys_len = self.msb_keys.len() * MsbKey::KEY_LEN;
+        for (i, key) in self.msb_keys.iter().enumerate() {
+            key.to_raw_line(raw_line_pointer.add(i * MsbKey::KEY_LEN));
+        }
+        let out =
+            slice::from_raw_parts_mut(raw_line_pointer.add(keys_len), 12 * self.triples.len());
+        for (triple, chunk) in self.triples.iter().zip(out.chunks_exact_mut(12)) {
+            chunk[0..4].copy_from_slice(&triple.a.to_le_bytes());
+            chunk[4..8].copy_from_slice(&triple.b.to_le_bytes());
+            chunk[8..12].copy_from_slice(&triple.c.to_le_bytes());
+        }
+    }
+
+    /// # Safety
+    /// Reads `MaxpoolMaterial::raw_len(k)` bytes
+    pub unsafe fn from_raw_line(raw_line_pointer: *const u8, k: usize) -> Self {
+        assert!(k > 0);
+        let n_comparisons = k - 1;
+        let msb_keys = (0..n_comparisons)
+            .map(|i| MsbKey::from_raw_line(raw_line_pointer.add(i * MsbKey::KEY_LEN)))
+            .collect();
+        let array = slice::from_raw_parts(
+            raw_line_pointer.add(n_comparisons * MsbKey::KEY_LEN),
+            12 * n_comparisons * (k + 1),
+        );
+        let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+        let triples = array
+            .chunks_exact(12)
+            .map(|chunk| BeaverTriple {
+                a: read_u32(&chunk[0..4]),
+                b: read_u32(&chunk[4..8]),
+                c: read_u32(&chunk[8..12]),
+            })
+            .collect();
+        MaxpoolMaterial {
+            k,
+            msb_keys,
+            triples,
+        }
+    }
+}
+
+// Current maximum of a window and its one-hot position.
+struct Candidate {
+    value: u32,
+    one_hot: Vec<u32>,
+}
+
+/// Shares of the max and of the one-hot argmax of each window of k values.
+/// `xs` holds the shares of the windows back to back, with one `MaxpoolMaterial` per window.
+/// The values are compared in two's complement order and their differences must fit in 31 bits.
+/// There are two calls to `channel.exchange` per level of the tournament, for all windows at once.
+pub fn maxpool(
+    prg: &mut impl Prg,
+    party_id: u8,
+    channel: &mut impl Channel,
+    xs: &[u32],
+    materials: &[MaxpoolMaterial],
+) -> (Vec<u32>, Vec<u32>) {
+    assert!((party_id == 0u8) || (party_id == 1u8));
+    assert!(!materials.is_empty());
+    let k = materials[0].k;
+    assert!(materials.iter().all(|material| material.k == k));
+    assert_eq!(xs.len(), k * materials.len());
+
+    // Each value starts as a candidate with a public one-hot position.
+    let mut windows: Vec<Vec<Candidate>> = xs
+        .chunks(k)
+        .map(|window| {
+            window
+                .iter()
+                .enumerate()
+                .map(|(i, &value)| {
+                    let mut one_hot = vec![0u32; k];
+                    one_hot[i] = party_id as u32;
+                    Candidate { value, one_hot }
+                })
+                .collect()
+        })
+        .collect();
+
+    // Material is consumed in the same order by both parties.
+    let mut next_key = 0;
+    let mut next_triple = 0;
+
+    while windows[0].len() > 1 {
+        // Open the masked differences of all the pairs of this level.
+        let mut masked = vec![];
+        for (window, material) in windows.iter().zip(materials.iter()) {
+            for (j, pair) in window.chunks_exact(2).enumerate() {
+                let key = &material.msb_keys[next_key + j];
+                masked.push(
+                    pair[0]
+                        .value
+                        .wrapping_sub(pair[1].value)
+                        .wrapping_add(key.alpha_share),
+                );
+            }
+        }
+        let masked = open(channel, &masked);
+
+        // c = 1[a >= b] = 1 - msb(a - b), then select with Beaver products c * (a - b).
+        let mut products = vec![];
+        let mut m = 0;
+        for (window, material) in windows.iter().zip(materials.iter()) {
+            for (j, pair) in window.chunks_exact(2).enumerate() {
+                let msb = material.msb_keys[next_key + j].eval(prg, party_id, masked[m]);
+                let c = (party_id as u32).wrapping_sub(msb);
+                m += 1;
+
+                products.push((c, pair[0].value.wrapping_sub(pair[1].value)));
+                for i in 0..k {
+                    products.push((c, pair[0].one_hot[i].wrapping_sub(pair[1].one_hot[i])));
+                }
+            }
+        }
+        let n_pairs = windows[0].len() / 2;
+        let triples: Vec<BeaverTriple> = materials
+            .iter()
+            .flat_map(|material| {
+                material.triples[next_triple..next_triple + n_pairs * (k + 1)].to_vec()
+            })
+            .collect();
+        let products = beaver_multiply(party_id, channel, &products, &triples);
+        next_key += n_pairs;
+        next_triple += n_pairs * (k + 1);
+
+        // The winner of each pair is b + c * (a - b), an odd candidate goes to the next level.
+        let mut products = products.into_iter();
+        windows = windows
+            .into_iter()
+            .map(|window| {
+                let mut next = Vec::with_capacity(window.len().div_ceil(2));
+                let mut window = window.into_iter();
+                while let Some(a) = window.next() {
+                    match window.next() {
+                        Some(b) => {
+                            let value = b.value.wrapping_add(products.next().unwrap());
+                            let one_hot = b
+                                .one_hot
+                                .iter()
+                                .map(|o| o.wrapping_add(products.next().unwrap()))
+                                .collect();
+                            next.push(Candidate { value, one_hot });
+                        }
+                        None => next.push(a),
+                    }
+                }
+                next
+            })
+            .collect();
+    }
+
+    let mut max = Vec::with_capacity(windows.len());
+    let mut argmax = Vec::with_capacity(windows.len() * k);
+    for mut window in windows {
+        let winner = window.pop().unwrap();
+        max.push(winner.value);
+        argmax.extend(winner.one_hot);
+    }
+    (max, argmax)
+}
+
+// Reconstructs the values from both shares.
+fn open(channel: &mut impl Channel, shares: &[u32]) -> Vec<u32> {
+    let other = channel.exchange(shares);
+    assert_eq!(other.len(), shares.len());
+    shares
+        .iter()
+        .zip(other.iter())
+        .map(|(x, y)| x.wrapping_add(*y))
+        .collect()
+}
+
+// Shares of x * y for each pair of shares, with one opening for the whole batch.
+fn beaver_multiply(
+    party_id: u8,
+    channel: &mut impl Channel,
+    pairs: &[(u32, u32)],
+    triples: &[BeaverTriple],
+) -> Vec<u32> {
+    assert_eq!(pairs.len(), triples.len());
+    let mut masked = Vec::with_capacity(2 * pairs.len());
+    for ((x, y), triple) in pairs.iter().zip(triples.iter()) {
+        masked.push(x.wrapping_sub(triple.a));
+        masked.push(y.wrapping_sub(triple.b));
+    }
+    let opened = open(channel, &masked);
+
+    // x * y = c + e * b + f * a + e * f, with e = x - a and f = y - b public.
+    triples
+        .iter()
+        .zip(opened.chunks_exact(2))
+        .map(|(triple, ef)| {
+            let (e, f) = (ef[0], ef[1]);
+            let mut z = triple
+                .c
+                .wrapping_add(e.wrapping_mul(triple.b))
+                .wrapping_add(f.wrapping_mul(triple.a));
+            if party_id == 1 {
+                z = z.wrapping_add(e.wrapping_mul(f));
+            }
+            z
+        })
+        .collect()
+}
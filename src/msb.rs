@@ -0,0 +1,274 @@
+//!
+//! Most significant bit extraction and bit decomposition keys, built on DIF
+//!
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use std::slice;
+
+use crate::fss::dif::{
+    eval_path, generate_cw_from_bits, read_tree_from_array, tree_len, write_tree_to_array,
+    CompressedCorrectionWord,
+};
+use crate::stream::{FSSKey, Prg, RawKey};
+use crate::utils::{
+    bit_decomposition, random_domain_element, read_domain_element, wrapping_sub_domain,
+    write_domain_element, Domain, Group,
+};
+
+/// MSB extraction key for inputs in T, with the output shared in G.
+/// With x + alpha public, msb(x) = msb(x + alpha) ^ msb(alpha) ^ carry,
+/// where carry = 1[x_low + alpha_low < alpha_low] on the lower bits is given by a DIF tree.
+/// The tree has one level per lower bit, so a key on the w lowest bits of T has w - 1 levels.
+#[derive(Debug)]
+pub struct GenericMsbKey<T: Domain, G: Group = u32> {
+    pub alpha_share: T,
+    pub z: G,
+    pub s: u128,
+    pub cw: Vec<CompressedCorrectionWord<G>>,
+    pub cw_leaf: Vec<G>,
+}
+
+/// MSB extraction key on 32 bits, used by the FFI.
+pub type MsbKey = GenericMsbKey<u32>;
+
+/// MSB extraction key on 32 bits with XOR shares, used by the FFI.
+pub type MsbKeyXor = GenericMsbKey<u32, bool>;
+
+/// Bit decomposition key for inputs in T, with one share in G per bit (least significant first).
+/// Bit i of x is the MSB of the i + 1 lowest bits of x, masked by the i + 1 lowest bits of alpha,
+/// so there is one MSB key per bit with a tree of i levels, i.e. 14660 bytes for u32.
+#[derive(Debug)]
+pub struct GenericBitDecompositionKey<T: Domain, G: Group = u32> {
+    pub alpha_share: T,
+    pub bits: Vec<GenericMsbKey<T, G>>,
+}
+
+/// Bit decomposition key on 32 bits, used by the FFI.
+pub type BitDecompositionKey = GenericBitDecompositionKey<u32>;
+
+/// Bit decomposition key on 32 bits with XOR shares, used by the FFI.
+pub type BitDecompositionKeyXor = GenericBitDecompositionKey<u32, bool>;
+
+// Size of an MSB key without its share of alpha, for a tree with `n_levels` levels.
+const fn msb_len<G: Group>(n_levels: usize) -> usize {
+    G::BYTES + tree_len::<G>(n_levels)
+}
+
+impl<T: Domain, G: Group> RawKey for GenericMsbKey<T, G> {
+    // N + 4 + 16 + 24 * (N * 8 - 1) + 4 * (N * 8), i.e. 896 bytes for u32
+    const KEY_LEN: usize = T::N + msb_len::<G>(T::N * 8 - 1);
+
+    unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
+        // Get a mutable reference to the output line.
+        let out_ref: &mut [u8] = slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN);
+        // Write the key.
+        write_domain_element(self.alpha_share, &mut out_ref[0..T::N]);
+        write_msb_to_array(self, &mut out_ref[T::N..]);
+    }
+
+    unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
+        let array = slice::from_raw_parts(key_pointer, Self::KEY_LEN);
+        let alpha_share = read_domain_element(&array[0..T::N]);
+        read_msb_from_array(alpha_share, &array[T::N..], T::N * 8 - 1)
+    }
+}
+
+impl<T: Domain, G: Group> FSSKey for GenericMsbKey<T, G> {
+    type Input = T;
+    type Output = G;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        // Random mask of the input, secret-shared between Alice and Bob.
+        let alpha: T = random_domain_element(rng);
+        let mask: T = random_domain_element(rng);
+        keypair_from_alpha(prg, rng, alpha, mask, T::N * 8)
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        assert!((party_id == 0u8) || (party_id == 1u8));
+        let top = 1u64 << self.cw.len();
+
+        // Share of d = msb(alpha) ^ carry, the tree only reads the bits below the msb.
+        let carry = eval_path(prg, party_id, self.s, &self.cw, &self.cw_leaf, x);
+        let d = self.z.add(carry);
+
+        // msb(x - alpha) = msb(x) ^ d
+        if x.to_u64() & top == 0 {
+            d
+        } else if party_id == 1 {
+            G::one().sub(d)
+        } else {
+            d.neg()
+        }
+    }
+}
+
+// MSB keys on the `width` lowest bits, for a given mask alpha shared as (alpha - mask, mask).
+fn keypair_from_alpha<T: Domain, G: Group>(
+    prg: &mut impl Prg,
+    rng: &mut (impl RngCore + CryptoRng),
+    alpha: T,
+    mask: T,
+    width: usize,
+) -> (GenericMsbKey<T, G>, GenericMsbKey<T, G>) {
+    let n_bits = T::N * 8;
+    let alpha_bits = bit_decomposition(alpha);
+    let alpha_high = alpha_bits[n_bits - width] == 1;
+
+    // Initialize seeds.
+    let s_a: u128 = rng.gen();
+    let s_b: u128 = rng.gen();
+
+    // The carry flips the msb of alpha: d = msb(alpha) + (1 - 2 * msb(alpha)) * carry,
+    // and the leaves share the carry on every x_low < alpha_low.
+    let (beta, z) = match alpha_high {
+        true => (G::one().neg(), G::one()),
+        false => (G::one(), G::zero()),
+    };
+    let (cw, cw_leaf) = generate_cw_from_bits(
+        prg,
+        &alpha_bits[n_bits - width + 1..],
+        beta,
+        G::zero(),
+        G::zero(),
+        s_a,
+        s_b,
+    );
+
+    // Secret-share msb(alpha) between Alice and Bob.
+    let z_mask = G::from_block(rng.gen());
+
+    // Return a key pair.
+    (
+        GenericMsbKey {
+            alpha_share: wrapping_sub_domain(alpha, mask),
+            z: z.sub(z_mask),
+            s: s_a,
+            cw: cw.clone(),
+            cw_leaf: cw_leaf.clone(),
+        },
+        GenericMsbKey {
+            alpha_share: mask,
+            z: z_mask,
+            s: s_b,
+            cw,
+            cw_leaf,
+        },
+    )
+}
+
+impl<T: Domain, G: Group> RawKey for GenericBitDecompositionKey<T, G> {
+    // N + one MSB key per bit with i levels, without their shares of alpha
+    const KEY_LEN: usize = T::N + bits_len::<G>(T::N * 8);
+
+    unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
+        let out_ref: &mut [u8] = slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN);
+        write_domain_element(self.alpha_share, &mut out_ref[0..T::N]);
+        let mut j = T::N;
+        for (i, key) in self.bits.iter().enumerate() {
+            write_msb_to_array(key, &mut out_ref[j..j + msb_len::<G>(i)]);
+            j += msb_len::<G>(i);
+        }
+    }
+
+    unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
+        let array = slice::from_raw_parts(key_pointer, Self::KEY_LEN);
+        let alpha_share = read_domain_element(&array[0..T::N]);
+        let mut j = T::N;
+        let mut bits = Vec::with_capacity(T::N * 8);
+        for i in 0..(T::N * 8) {
+            bits.push(read_msb_from_array(alpha_share, &array[j..], i));
+            j += msb_len::<G>(i);
+        }
+        GenericBitDecompositionKey { alpha_share, bits }
+    }
+}
+
+// Size of the MSB keys of the `n_bits` lowest bits.
+const fn bits_len<G: Group>(n_bits: usize) -> usize {
+    let mut len = 0;
+    let mut i = 0;
+    while i < n_bits {
+        len += msb_len::<G>(i);
+        i += 1;
+    }
+    len
+}
+
+impl<T: Domain, G: Group> GenericBitDecompositionKey<T, G> {
+    /// Keys for the bits of x, evaluated on x + alpha where alpha is a random mask
+    /// shared between the keys.
+    pub fn generate_keypair(prg: &mut impl Prg) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, &mut rand::thread_rng())
+    }
+
+    /// Same as `generate_keypair`, with all the randomness drawn from `rng`.
+    pub fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        let n_bits = T::N * 8;
+
+        // Random mask of the input, secret-shared between Alice and Bob.
+        let alpha: T = random_domain_element(rng);
+        let mask: T = random_domain_element(rng);
+
+        let mut bits_a = Vec::with_capacity(n_bits);
+        let mut bits_b = Vec::with_capacity(n_bits);
+        for i in 0..n_bits {
+            let (k_a, k_b) = keypair_from_alpha(prg, rng, alpha, mask, i + 1);
+            bits_a.push(k_a);
+            bits_b.push(k_b);
+        }
+
+        (
+            GenericBitDecompositionKey {
+                alpha_share: wrapping_sub_domain(alpha, mask),
+                bits: bits_a,
+            },
+            GenericBitDecompositionKey {
+                alpha_share: mask,
+                bits: bits_b,
+            },
+        )
+    }
+
+    /// Shares of the bits of x, least significant first, where x + alpha is the public input.
+    pub fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> Vec<G> {
+        self.bits
+            .iter()
+            .map(|key| key.eval(prg, party_id, x))
+            .collect()
+    }
+}
+
+//
+// Serialization functions
+//
+
+// Writes the share of msb(alpha) and the tree, i.e. `msb_len(key.cw.len())` bytes.
+fn write_msb_to_array<T: Domain, G: Group>(key: &GenericMsbKey<T, G>, array: &mut [u8]) {
+    key.z.write_le_bytes(&mut array[0..G::BYTES]);
+    write_tree_to_array(key.s, &key.cw, &key.cw_leaf, &mut array[G::BYTES..]);
+}
+
+fn read_msb_from_array<T: Domain, G: Group>(
+    alpha_share: T,
+    array: &[u8],
+    n_levels: usize,
+) -> GenericMsbKey<T, G> {
+    let z = G::read_le_bytes(&array[0..G::BYTES]);
+    let (s, cw, cw_leaf) = read_tree_from_array(&array[G::BYTES..], n_levels);
+
+    GenericMsbKey {
+        alpha_share,
+        z,
+        s,
+        cw,
+        cw_leaf,
+    }
+}
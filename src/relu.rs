@@ -0,0 +1,143 @@
+//!
+//! ReLU keys on signed 32-bit inputs, with a DIF comparison on the lower 31 bits
+//!
+
+use rand::{CryptoRng, Rng, RngCore};
+use std::convert::TryInto;
+
+use std::slice;
+
+use crate::fss::dif::{
+    eval_path, generate_cw_from_bits, read_tree_from_array, tree_len, write_tree_to_array,
+    CompressedCorrectionWord,
+};
+use crate::stream::{FSSKey, Prg, RawKey};
+use crate::utils::{bit_decomposition, Group, Pair};
+use crate::N;
+
+// Lower 31 bits of the inputs.
+const LOW_MASK: u32 = (1 << 31) - 1;
+
+/// ReLU key on two's complement 32-bit inputs, with shares in Z/2^32Z.
+/// The DIF tree carries the pair (1 - 2 * msb(alpha)) * (1, alpha) as payload,
+/// so eval returns a share of max(x, 0) where x + alpha is the public input.
+#[derive(Debug)]
+pub struct ReluKey {
+    pub alpha_share: u32,
+    pub z: Pair<u32>,
+    pub s: u128,
+    pub cw: Vec<CompressedCorrectionWord<Pair<u32>>>,
+    pub cw_leaf: Vec<Pair<u32>>,
+}
+
+impl RawKey for ReluKey {
+    // 4 + 8 + 16 + 28 * 31 + 8 * 32
+    const KEY_LEN: usize = N + 8 + tree_len::<Pair<u32>>(N * 8 - 1);
+
+    unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
+        // Get a mutable reference to the output line.
+        let out_ref: &mut [u8] = slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN);
+        // Write the key.
+        write_key_to_array(self, out_ref);
+    }
+
+    unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
+        read_key_from_array(slice::from_raw_parts(key_pointer, Self::KEY_LEN))
+    }
+}
+
+impl FSSKey for ReluKey {
+    type Input = u32;
+    type Output = u32;
+
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        // Random mask of the input.
+        let alpha: u32 = rng.gen();
+        let msb = alpha >> 31;
+
+        // Initialize seeds.
+        let s_a: u128 = rng.gen();
+        let s_b: u128 = rng.gen();
+
+        // The carry of the lower bits is 1[x_low < alpha_low], it flips the msb of alpha.
+        // The tree only has one level per lower bit.
+        let sign = 1u32.wrapping_sub(2 * msb);
+        let beta = Pair(sign, sign.wrapping_mul(alpha));
+        let zero = Pair::zero();
+        let alpha_bits = bit_decomposition(alpha);
+        let (cw, cw_leaf) =
+            generate_cw_from_bits(prg, &alpha_bits[1..], beta, zero, zero, s_a, s_b);
+
+        // Secret-share alpha and the msb offsets between Alice and Bob.
+        let mask: u32 = rng.gen();
+        let z = Pair(msb, msb.wrapping_mul(alpha));
+        let z_mask = Pair::from_block(rng.gen());
+
+        // Return a key pair.
+        (
+            ReluKey {
+                alpha_share: alpha.wrapping_sub(mask),
+                z: z.sub(z_mask),
+                s: s_a,
+                cw: cw.clone(),
+                cw_leaf: cw_leaf.clone(),
+            },
+            ReluKey {
+                alpha_share: mask,
+                z: z_mask,
+                s: s_b,
+                cw,
+                cw_leaf,
+            },
+        )
+    }
+
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32 {
+        assert!((party_id == 0u8) || (party_id == 1u8));
+        let c = x >> 31;
+
+        // Shares of d = msb(alpha) ^ carry and of d * alpha.
+        let carry = eval_path(prg, party_id, self.s, &self.cw, &self.cw_leaf, x & LOW_MASK);
+        let Pair(d, d_alpha) = self.z.add(carry);
+
+        // msb(x - alpha) = c ^ d, so 1[x - alpha >= 0] = (1 - c) - (1 - 2c) * d.
+        let sign = 1u32.wrapping_sub(2 * c);
+        let mut b = 0u32.wrapping_sub(sign.wrapping_mul(d));
+        if party_id == 1 {
+            b = b.wrapping_add(1 - c);
+        }
+        let b_alpha = (1 - c)
+            .wrapping_mul(self.alpha_share)
+            .wrapping_sub(sign.wrapping_mul(d_alpha));
+
+        // The final value is a share of 1[x - alpha >= 0] * (x - alpha)
+        x.wrapping_mul(b).wrapping_sub(b_alpha)
+    }
+}
+
+//
+// Serialization functions
+//
+
+fn write_key_to_array(key: &ReluKey, array: &mut [u8]) {
+    array[0..N].copy_from_slice(&key.alpha_share.to_le_bytes());
+    key.z.write_le_bytes(&mut array[N..N + 8]);
+    write_tree_to_array(key.s, &key.cw, &key.cw_leaf, &mut array[N + 8..]);
+}
+
+fn read_key_from_array(array: &[u8]) -> ReluKey {
+    let alpha_share = u32::from_le_bytes(array[0..N].try_into().unwrap());
+    let z = Pair::read_le_bytes(&array[N..N + 8]);
+    let (s, cw, cw_leaf) = read_tree_from_array(&array[N + 8..], N * 8 - 1);
+
+    ReluKey {
+        alpha_share,
+        z,
+        s,
+        cw,
+        cw_leaf,
+    }
+}
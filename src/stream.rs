@@ -2,17 +2,58 @@
 //! Utilities to iterate over Numpy arrays
 //!
 
+use rand::{CryptoRng, RngCore};
+use std::convert::TryFrom;
 use std::slice;
 
-use crate::eq::EqKey;
-use crate::le::LeKey;
-use crate::utils::Mmo;
+use crate::eq::{EqKey, EqKey64, EqKeyXor};
+use crate::interval::IntervalKey;
+use crate::le::{GeKey, GtKey, LeKey, LeKey64, LeKeyXor, LtKey, SignedLeKey};
+use crate::msb::{BitDecompositionKey, BitDecompositionKeyXor, MsbKey, MsbKeyXor};
+use crate::relu::ReluKey;
+use crate::trunc::TruncKey;
+use crate::utils::{
+    aes_keys_fingerprint, seeded_rng, ChaCha12Prg, ChaCha20Prg, ChaCha8Prg, Domain, Group, Mmo,
+};
 use crate::N;
 
 pub trait FSSKey: Sized {
-    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: u32) -> u32;
+    type Input: Domain;
+    type Output: Group;
 
-    fn generate_keypair(prg: &mut impl Prg) -> (Self, Self);
+    fn eval(&self, prg: &mut impl Prg, party_id: u8, x: Self::Input) -> Self::Output;
+
+    /// Evaluates keys[i] on xs[i], e.g. with the trees of the keys walked in lockstep
+    /// so that the Prg expands several seeds at once.
+    fn eval_batch(
+        keys: &[Self],
+        prg: &mut impl Prg,
+        party_id: u8,
+        xs: &[Self::Input],
+        out: &mut [Self::Output],
+    ) {
+        assert_eq!(keys.len(), xs.len());
+        assert_eq!(keys.len(), out.len());
+        for ((key, &x), out) in keys.iter().zip(xs.iter()).zip(out.iter_mut()) {
+            *out = key.eval(prg, party_id, x);
+        }
+    }
+
+    /// Keys with all the randomness (alpha, seeds and masks) drawn from `rng`.
+    fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self);
+
+    /// Keys drawn from the thread randomness.
+    fn generate_keypair(prg: &mut impl Prg) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, &mut rand::thread_rng())
+    }
+
+    /// Reproducible keys from a 128-bit seed, e.g. for test vectors.
+    fn generate_keypair_from_seed(prg: &mut impl Prg, seed: u128) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, &mut seeded_rng(seed))
+    }
 }
 
 pub trait RawKey: Sized {
@@ -29,18 +70,93 @@ pub trait RawKey: Sized {
 
 // Keyed Prg
 pub trait Prg {
+    /// Prg from any number of public keys: the first `EXPANSION` keys are used,
+    /// and missing keys are zero keys. The `new` constructors take exactly `EXPANSION` keys.
     fn from_slice(key: &[u128]) -> Self;
 
     fn from_vec(key: &[u128]) -> Self;
 
-    // NOTE: Rust Stable does not have const generics
-    // const expansion_factor: usize;
-    // fn expand(&mut self, seed: u128) -> [u128; Self::expansion_factor];
-    fn expand(&mut self, seed: u128) -> Vec<u128>;
+    /// Number of blocks of one expansion, e.g. the number of AES keys of `Mmo`.
+    const EXPANSION: usize;
+
+    /// Writes the first `out.len()` blocks of the expansion, with `out.len() <= EXPANSION`.
+    fn expand_into(&mut self, seed: u128, out: &mut [u128]);
+
+    /// The `EXPANSION` blocks of the expansion, on the heap. Prefer `expand_array`.
+    fn expand(&mut self, seed: u128) -> Vec<u128> {
+        let mut out = vec![0u128; Self::EXPANSION];
+        self.expand_into(seed, &mut out);
+        out
+    }
+
+    /// The first K blocks of the expansion, without heap allocation.
+    /// Asking for more than `EXPANSION` blocks does not compile.
+    fn expand_array<const K: usize>(&mut self, seed: u128) -> [u128; K] {
+        const { assert!(K <= Self::EXPANSION, "The Prg expands to fewer blocks") };
+        let mut out = [0u128; K];
+        self.expand_into(seed, &mut out);
+        out
+    }
+
+    /// The first K blocks of the expansion of each seed, with one array per seed in `out`.
+    /// Implementations can expand the seeds in parallel, e.g. with pipelined AES.
+    fn expand_batch<const K: usize>(&mut self, seeds: &[u128], out: &mut [[u128; K]]) {
+        assert_eq!(seeds.len(), out.len());
+        for (seed, out) in seeds.iter().zip(out.iter_mut()) {
+            *out = self.expand_array(*seed);
+        }
+    }
 
     // TODO: key type, read/write state to line
 }
 
+/// Prgs available through the FFI. Keys can record the Prg they were generated with
+/// and a fingerprint of its public keys in a header of `PRG_HEADER_LEN` bytes,
+/// so that eval refuses keys from another Prg or from other public keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrgId {
+    Mmo = 0,
+    ChaCha8 = 1,
+    ChaCha12 = 2,
+    ChaCha20 = 3,
+}
+
+/// One byte for the `PrgId`, then 8 bytes of fingerprint of the public keys.
+pub const PRG_HEADER_LEN: usize = 1 + 8;
+
+impl PrgId {
+    pub fn from_id(prg_id: usize) -> Self {
+        match prg_id {
+            0 => PrgId::Mmo,
+            1 => PrgId::ChaCha8,
+            2 => PrgId::ChaCha12,
+            3 => PrgId::ChaCha20,
+            _ => panic!("Unknown prg_id: {}", prg_id),
+        }
+    }
+}
+
+/// Header of the key lines: the Prg id, then the fingerprint of its public keys.
+pub fn prg_header(prg_id: PrgId, aes_keys: &[u128]) -> [u8; PRG_HEADER_LEN] {
+    let mut header = [0u8; PRG_HEADER_LEN];
+    header[0] = prg_id as u8;
+    header[1..].copy_from_slice(&aes_keys_fingerprint(aes_keys).to_le_bytes());
+    header
+}
+
+/// Blocks per expansion of the Prgs of the FFI, enough for the DIF trees.
+const FFI_EXPANSION: usize = 3;
+
+// The public keys of the op, padded with zero keys that the DPF trees never use.
+fn ffi_keys(aes_keys: &[u128]) -> [u128; FFI_EXPANSION] {
+    assert!(aes_keys.len() <= FFI_EXPANSION);
+    let mut keys = [0u128; FFI_EXPANSION];
+    keys[..aes_keys.len()].copy_from_slice(aes_keys);
+    keys
+}
+
+/// Keys of `K::KEY_LEN` bytes, with a header of `PRG_HEADER_LEN` bytes if `with_header`.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_key_stream(
     aes_keys: &[u128],
     _stream_id: usize,
@@ -48,30 +164,187 @@ pub fn generate_key_stream(
     key_a_pointer: usize,
     key_b_pointer: usize,
     op_id: usize,
+    params: &[u64],
+    prg_id: PrgId,
+    with_header: bool,
 ) {
     // Generate keys in sequence
     let key_a_p = key_a_pointer as *mut u8;
     let key_b_p = key_b_pointer as *mut u8;
+    let header = if with_header {
+        Some(prg_header(prg_id, aes_keys))
+    } else {
+        None
+    };
+
+    unsafe {
+        match prg_id {
+            PrgId::Mmo => generate_key_stream_with(
+                &mut Mmo::new(&ffi_keys(aes_keys)),
+                stream_length,
+                key_a_p,
+                key_b_p,
+                header,
+                op_id,
+                params,
+            ),
+            PrgId::ChaCha8 => generate_key_stream_with(
+                &mut ChaCha8Prg::new(&ffi_keys(aes_keys)),
+                stream_length,
+                key_a_p,
+                key_b_p,
+                header,
+                op_id,
+                params,
+            ),
+            PrgId::ChaCha12 => generate_key_stream_with(
+                &mut ChaCha12Prg::new(&ffi_keys(aes_keys)),
+                stream_length,
+                key_a_p,
+                key_b_p,
+                header,
+                op_id,
+                params,
+            ),
+            PrgId::ChaCha20 => generate_key_stream_with(
+                &mut ChaCha20Prg::new(&ffi_keys(aes_keys)),
+                stream_length,
+                key_a_p,
+                key_b_p,
+                header,
+                op_id,
+                params,
+            ),
+        }
+    }
+}
 
-    // TODO: def. Impl Prg.
-    let mut prg = Mmo::from_vec(aes_keys);
+/// # Safety
+/// Writes `stream_length` keys of the op to each raw pointer
+unsafe fn generate_key_stream_with<P: Prg>(
+    prg: &mut P,
+    stream_length: usize,
+    key_a_p: *mut u8,
+    key_b_p: *mut u8,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+    op_id: usize,
+    params: &[u64],
+) {
+    match op_id {
+        0 => generate_keys::<EqKey>(prg, stream_length, key_a_p, key_b_p, header),
+        1 => generate_keys::<LeKey>(prg, stream_length, key_a_p, key_b_p, header),
+        2 => generate_keys::<EqKey64>(prg, stream_length, key_a_p, key_b_p, header),
+        3 => generate_keys::<LeKey64>(prg, stream_length, key_a_p, key_b_p, header),
+        4 => generate_keys::<EqKeyXor>(prg, stream_length, key_a_p, key_b_p, header),
+        5 => generate_keys::<LeKeyXor>(prg, stream_length, key_a_p, key_b_p, header),
+        6 => {
+            // Public bounds of the interval
+            assert!(params.len() >= 2, "op_id 6 expects [lower, upper]");
+            let (lower, upper) = (params[0] as u32, params[1] as u32);
+            generate_keys_with(prg, stream_length, key_a_p, key_b_p, header, |prg| {
+                IntervalKey::generate_keypair(prg, lower, upper)
+            })
+        }
+        7 => generate_keys::<ReluKey>(prg, stream_length, key_a_p, key_b_p, header),
+        8 => generate_keys::<SignedLeKey>(prg, stream_length, key_a_p, key_b_p, header),
+        9 => generate_keys::<LtKey>(prg, stream_length, key_a_p, key_b_p, header),
+        10 => generate_keys::<GtKey>(prg, stream_length, key_a_p, key_b_p, header),
+        11 => generate_keys::<GeKey>(prg, stream_length, key_a_p, key_b_p, header),
+        14 => generate_keys::<MsbKey>(prg, stream_length, key_a_p, key_b_p, header),
+        15 => generate_keys::<MsbKeyXor>(prg, stream_length, key_a_p, key_b_p, header),
+        16 => generate_keys_with(prg, stream_length, key_a_p, key_b_p, header, |prg| {
+            BitDecompositionKey::generate_keypair(prg)
+        }),
+        17 => generate_keys_with(prg, stream_length, key_a_p, key_b_p, header, |prg| {
+            BitDecompositionKeyXor::generate_keypair(prg)
+        }),
+        18 => {
+            // [alpha, mask] for each key
+            assert_eq!(
+                params.len(),
+                2 * stream_length,
+                "op_id {} expects [alpha, mask] pairs",
+                op_id
+            );
+            let mut alphas = params.chunks_exact(2);
+            generate_keys_with(prg, stream_length, key_a_p, key_b_p, header, |prg| {
+                let p = alphas.next().unwrap();
+                let (alpha, mask) = (p[0] as u32, p[1] as u32);
+                EqKey::generate_keypair_from_alpha(prg, alpha, mask, &mut rand::thread_rng())
+            })
+        }
+        19 => {
+            assert_eq!(
+                params.len(),
+                2 * stream_length,
+                "op_id {} expects [alpha, mask] pairs",
+                op_id
+            );
+            let mut alphas = params.chunks_exact(2);
+            generate_keys_with(prg, stream_length, key_a_p, key_b_p, header, |prg| {
+                let p = alphas.next().unwrap();
+                let (alpha, mask) = (p[0] as u32, p[1] as u32);
+                LeKey::generate_keypair_from_alpha(prg, alpha, mask, &mut rand::thread_rng())
+            })
+        }
+        12 | 13 => {
+            // Number of truncated bits
+            assert!(!params.is_empty(), "op_id {} expects [f]", op_id);
+            let f = match u8::try_from(params[0]) {
+                Ok(f) if f > 0 && (f as usize) < N * 8 => f,
+                _ => panic!(
+                    "op_id {} expects 0 < f < {}, got {}",
+                    op_id,
+                    N * 8,
+                    params[0]
+                ),
+            };
+            generate_keys_with(prg, stream_length, key_a_p, key_b_p, header, |prg| {
+                TruncKey::generate_keypair(prg, f)
+            })
+        }
+        _ => panic!("Unknown op_id: {}", op_id),
+    }
+}
 
+/// # Safety
+/// Writes `stream_length` lines of `K::KEY_LEN` bytes to each raw pointer,
+/// after the header if any
+unsafe fn generate_keys<K: FSSKey + RawKey>(
+    prg: &mut impl Prg,
+    stream_length: usize,
+    key_a_p: *mut u8,
+    key_b_p: *mut u8,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+) {
+    generate_keys_with(prg, stream_length, key_a_p, key_b_p, header, |prg| {
+        K::generate_keypair(prg)
+    })
+}
+
+/// # Safety
+/// Writes `stream_length` lines of `K::KEY_LEN` bytes to each raw pointer,
+/// for keys that need more than a Prg to be generated
+unsafe fn generate_keys_with<K: RawKey, P: Prg>(
+    prg: &mut P,
+    stream_length: usize,
+    key_a_p: *mut u8,
+    key_b_p: *mut u8,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+    mut keygen: impl FnMut(&mut P) -> (K, K),
+) {
+    let header_len = header.map_or(0, |_| PRG_HEADER_LEN);
+    let line_len = header_len + K::KEY_LEN;
     for line_counter in 0..stream_length {
-        if op_id == 0 {
-            let (key_a, key_b) = EqKey::generate_keypair(&mut prg);
-            let key_len = EqKey::KEY_LEN;
-            unsafe {
-                key_a.to_raw_line(key_a_p.add(key_len * line_counter));
-                key_b.to_raw_line(key_b_p.add(key_len * line_counter));
-            }
-        } else {
-            let (key_a, key_b) = LeKey::generate_keypair(&mut prg);
-            let key_len = LeKey::KEY_LEN;
-            unsafe {
-                key_a.to_raw_line(key_a_p.add(key_len * line_counter));
-                key_b.to_raw_line(key_b_p.add(key_len * line_counter));
-            }
+        let (key_a, key_b) = keygen(prg);
+        let line_a_p = key_a_p.add(line_len * line_counter);
+        let line_b_p = key_b_p.add(line_len * line_counter);
+        if let Some(header) = header {
+            slice::from_raw_parts_mut(line_a_p, PRG_HEADER_LEN).copy_from_slice(&header);
+            slice::from_raw_parts_mut(line_b_p, PRG_HEADER_LEN).copy_from_slice(&header);
         }
+        key_a.to_raw_line(line_a_p.add(header_len));
+        key_b.to_raw_line(line_b_p.add(header_len));
     }
 }
 
@@ -85,33 +358,259 @@ pub fn eval_key_stream(
     key_pointer: usize,
     result_pointer: usize,
     op_id: usize,
+    prg_id: PrgId,
+    with_header: bool,
 ) {
     assert!((party_id == 0u8) || (party_id == 1u8));
 
-    let mut prg = Mmo::from_vec(aes_keys);
-
     // Read, eval, write line by line
 
-    let x_pointer_p = x_pointer as *const u8;
-    let key_pointer_p = key_pointer as *const u8;
-    let result_ptr_p = result_pointer as *mut i64;
+    let x_p = x_pointer as *const u8;
+    let key_p = key_pointer as *const u8;
+    let result_p = result_pointer as *mut i64;
+    let header = if with_header {
+        Some(prg_header(prg_id, aes_keys))
+    } else {
+        None
+    };
+
+    unsafe {
+        match prg_id {
+            PrgId::Mmo => eval_key_stream_with(
+                &mut Mmo::new(&ffi_keys(aes_keys)),
+                party_id,
+                stream_length,
+                x_p,
+                key_p,
+                result_p,
+                header,
+                op_id,
+            ),
+            PrgId::ChaCha8 => eval_key_stream_with(
+                &mut ChaCha8Prg::new(&ffi_keys(aes_keys)),
+                party_id,
+                stream_length,
+                x_p,
+                key_p,
+                result_p,
+                header,
+                op_id,
+            ),
+            PrgId::ChaCha12 => eval_key_stream_with(
+                &mut ChaCha12Prg::new(&ffi_keys(aes_keys)),
+                party_id,
+                stream_length,
+                x_p,
+                key_p,
+                result_p,
+                header,
+                op_id,
+            ),
+            PrgId::ChaCha20 => eval_key_stream_with(
+                &mut ChaCha20Prg::new(&ffi_keys(aes_keys)),
+                party_id,
+                stream_length,
+                x_p,
+                key_p,
+                result_p,
+                header,
+                op_id,
+            ),
+        }
+    }
+}
+
+/// # Safety
+/// Reads `stream_length` inputs and keys of the op, writes their results
+#[allow(clippy::too_many_arguments)]
+unsafe fn eval_key_stream_with<P: Prg>(
+    prg: &mut P,
+    party_id: u8,
+    stream_length: usize,
+    x_p: *const u8,
+    key_p: *const u8,
+    result_p: *mut i64,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+    op_id: usize,
+) {
+    match op_id {
+        0 => eval_keys::<EqKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        1 => eval_keys::<LeKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        2 => eval_keys::<EqKey64>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        3 => eval_keys::<LeKey64>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        4 => eval_keys::<EqKeyXor>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        5 => eval_keys::<LeKeyXor>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        6 => eval_keys_with(
+            prg,
+            stream_length,
+            x_p,
+            key_p,
+            result_p,
+            header,
+            |prg, key: &IntervalKey, x| key.eval(prg, party_id, x),
+        ),
+        7 => eval_keys::<ReluKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        8 => eval_keys::<SignedLeKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        9 => eval_keys::<LtKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        10 => eval_keys::<GtKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        11 => eval_keys::<GeKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        12 => eval_keys_with(
+            prg,
+            stream_length,
+            x_p,
+            key_p,
+            result_p,
+            header,
+            |prg, key: &TruncKey, x| key.eval(prg, party_id, x),
+        ),
+        13 => eval_keys_with(
+            prg,
+            stream_length,
+            x_p,
+            key_p,
+            result_p,
+            header,
+            |prg, key: &TruncKey, x| key.eval_arithmetic(prg, party_id, x),
+        ),
+        14 => eval_keys::<MsbKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        15 => eval_keys::<MsbKeyXor>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        16 => eval_keys_vec(
+            prg,
+            stream_length,
+            x_p,
+            key_p,
+            result_p,
+            header,
+            |prg, key: &BitDecompositionKey, x| key.eval(prg, party_id, x),
+        ),
+        17 => eval_keys_vec(
+            prg,
+            stream_length,
+            x_p,
+            key_p,
+            result_p,
+            header,
+            |prg, key: &BitDecompositionKeyXor, x| key.eval(prg, party_id, x),
+        ),
+        // Same keys as ops 0 and 1
+        18 => eval_keys::<EqKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        19 => eval_keys::<LeKey>(prg, party_id, stream_length, x_p, key_p, result_p, header),
+        _ => panic!("Unknown op_id: {}", op_id),
+    }
+}
+
+/// Number of lines given at once to `FSSKey::eval_batch`, i.e. the AES-NI pipeline width.
+const EVAL_BATCH: usize = 8;
 
+/// # Safety
+/// Reads `stream_length` inputs of `N` bytes and keys of `K::KEY_LEN` bytes,
+/// writes `stream_length` results, evaluated in batches of `EVAL_BATCH` lines
+unsafe fn eval_keys<K: FSSKey<Input = u32> + RawKey>(
+    prg: &mut impl Prg,
+    party_id: u8,
+    stream_length: usize,
+    x_pointer_p: *const u8,
+    key_pointer_p: *const u8,
+    result_ptr_p: *mut i64,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+) {
+    let mut keys: Vec<K> = Vec::with_capacity(EVAL_BATCH);
+    let mut xs: Vec<u32> = Vec::with_capacity(EVAL_BATCH);
+    let mut results = [K::Output::zero(); EVAL_BATCH];
+    for batch_start in (0..stream_length).step_by(EVAL_BATCH) {
+        let lines = batch_start..(batch_start + EVAL_BATCH).min(stream_length);
+        keys.clear();
+        xs.clear();
+        for line_counter in lines.clone() {
+            xs.push(read_input(x_pointer_p, line_counter));
+            keys.push(read_key_line::<K>(key_pointer_p, line_counter, header));
+        }
+        let results = &mut results[0..keys.len()];
+        K::eval_batch(&keys, prg, party_id, &xs, results);
+        for (line_counter, result) in lines.zip(results.iter()) {
+            *(result_ptr_p.add(line_counter)) = result.to_i64();
+        }
+    }
+}
+
+/// # Safety
+/// Reads the input of a line, on `N` bytes
+unsafe fn read_input(x_pointer_p: *const u8, line_counter: usize) -> u32 {
+    let x_ptr: *const [u8; N] =
+        slice::from_raw_parts(x_pointer_p.add(N * line_counter), N).as_ptr() as *const [u8; N];
+    u32::from_le_bytes(*x_ptr)
+}
+
+/// # Safety
+/// Reads the key of a line, after checking that its header is the Prg and public keys of eval
+unsafe fn read_key_line<K: RawKey>(
+    key_pointer_p: *const u8,
+    line_counter: usize,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+) -> K {
+    match header {
+        None => K::from_raw_line(key_pointer_p.add(K::KEY_LEN * line_counter)),
+        Some(header) => {
+            let line_p = key_pointer_p.add((PRG_HEADER_LEN + K::KEY_LEN) * line_counter);
+            let line_header = slice::from_raw_parts(line_p, PRG_HEADER_LEN);
+            assert_eq!(
+                line_header[0], header[0],
+                "Key generated with prg_id {}, evaluated with prg_id {}",
+                line_header[0], header[0]
+            );
+            assert_eq!(
+                line_header[1..],
+                header[1..],
+                "Key generated with other public keys than the ones given to eval"
+            );
+            K::from_raw_line(line_p.add(PRG_HEADER_LEN))
+        }
+    }
+}
+
+/// # Safety
+/// Reads `stream_length` inputs of `N` bytes and keys of `K::KEY_LEN` bytes,
+/// writes `stream_length` results, for keys that do not implement `FSSKey`
+unsafe fn eval_keys_with<K: RawKey, G: Group, P: Prg>(
+    prg: &mut P,
+    stream_length: usize,
+    x_pointer_p: *const u8,
+    key_pointer_p: *const u8,
+    result_ptr_p: *mut i64,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+    mut eval: impl FnMut(&mut P, &K, u32) -> G,
+) {
+    for line_counter in 0..stream_length {
+        // Read key and value to evaluate
+        let x = read_input(x_pointer_p, line_counter);
+
+        let key = read_key_line::<K>(key_pointer_p, line_counter, header);
+        let result = eval(prg, &key, x);
+        *(result_ptr_p.add(line_counter)) = result.to_i64();
+    }
+}
+
+/// # Safety
+/// Reads `stream_length` inputs of `N` bytes and keys of `K::KEY_LEN` bytes,
+/// writes `N * 8` results for each input, e.g. one per bit
+unsafe fn eval_keys_vec<K: RawKey, G: Group, P: Prg>(
+    prg: &mut P,
+    stream_length: usize,
+    x_pointer_p: *const u8,
+    key_pointer_p: *const u8,
+    result_ptr_p: *mut i64,
+    header: Option<[u8; PRG_HEADER_LEN]>,
+    mut eval: impl FnMut(&mut P, &K, u32) -> Vec<G>,
+) {
     for line_counter in 0..stream_length {
         // Read key and value to evaluate
-        unsafe {
-            let x_ptr: *const [u8; N] = slice::from_raw_parts(x_pointer_p.add(N * line_counter), N)
-                .as_ptr() as *const [u8; N];
-            let x: u32 = u32::from_le_bytes(*x_ptr);
-
-            if op_id == 0 {
-                let key = EqKey::from_raw_line(key_pointer_p.add(EqKey::KEY_LEN * line_counter));
-                let result: u32 = key.eval(&mut prg, party_id, x);
-                *(result_ptr_p.add(line_counter)) = result as i64;
-            } else {
-                let key = LeKey::from_raw_line(key_pointer_p.add(LeKey::KEY_LEN * line_counter));
-                let result: u32 = key.eval(&mut prg, party_id, x);
-                *(result_ptr_p.add(line_counter)) = result as i64;
-            }
+        let x = read_input(x_pointer_p, line_counter);
+
+        let key = read_key_line::<K>(key_pointer_p, line_counter, header);
+        let results = eval(prg, &key, x);
+        assert_eq!(results.len(), N * 8);
+        for (i, result) in results.into_iter().enumerate() {
+            *(result_ptr_p.add(N * 8 * line_counter + i)) = result.to_i64();
         }
     }
 }
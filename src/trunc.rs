@@ -0,0 +1,233 @@
+//!
+//! Truncation keys for fixed-point arithmetic, following the truncation gate of Boyle et al.
+//! (https://eprint.iacr.org/2020/1392)
+//!
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use std::slice;
+
+use crate::fss::dif::{
+    eval_path, generate_cw_from_bits, read_tree_from_array, tree_len, write_tree_to_array,
+    CompressedCorrectionWord,
+};
+use crate::stream::{Prg, RawKey};
+use crate::utils::{
+    bit_decomposition, random_domain_element, read_domain_element, wrapping_add_domain,
+    wrapping_sub_domain, write_domain_element, Domain, Group,
+};
+
+/// Truncation key by f bits on inputs in T, with shares in G.
+/// With x + alpha public, x >> f = x_h - alpha_h + 2^(n - f) * 1[x + alpha < alpha] - 1[x_l + alpha_l < alpha_l],
+/// where _h and _l are the bits above and below f. Each indicator has its own DIF tree,
+/// with one level per bit of T for the wrap-around and f levels for the borrow.
+#[derive(Debug)]
+pub struct GenericTruncKey<T: Domain, G: Group = u32> {
+    pub alpha_share: T,
+    pub high_share: G,
+    pub f: u8,
+    pub s_wrap: u128,
+    pub cw_wrap: Vec<CompressedCorrectionWord<G>>,
+    pub cw_leaf_wrap: Vec<G>,
+    pub s_low: u128,
+    pub cw_low: Vec<CompressedCorrectionWord<G>>,
+    pub cw_leaf_low: Vec<G>,
+}
+
+/// Truncation key on 32 bits, used by the FFI.
+pub type TruncKey = GenericTruncKey<u32>;
+
+impl<T: Domain, G: Group> RawKey for GenericTruncKey<T, G> {
+    // N + G + 1 + both trees, with room for the largest borrow tree, i.e. 1813 bytes for u32
+    const KEY_LEN: usize =
+        T::N + G::BYTES + 1 + tree_len::<G>(T::N * 8) + tree_len::<G>(T::N * 8 - 1);
+
+    unsafe fn to_raw_line(&self, key_pointer: *mut u8) {
+        // Get a mutable reference to the output line.
+        let out_ref: &mut [u8] = slice::from_raw_parts_mut(key_pointer, Self::KEY_LEN);
+        // Write the key.
+        write_key_to_array(self, out_ref);
+    }
+
+    unsafe fn from_raw_line(key_pointer: *const u8) -> Self {
+        read_key_from_array(slice::from_raw_parts(key_pointer, Self::KEY_LEN))
+    }
+}
+
+impl<T: Domain, G: Group> GenericTruncKey<T, G> {
+    /// Keys to truncate by 0 < f < N * 8 bits, evaluated on x + alpha
+    /// where alpha is a random mask shared between the keys.
+    pub fn generate_keypair(prg: &mut impl Prg, f: u8) -> (Self, Self) {
+        Self::generate_keypair_from_rng(prg, f, &mut rand::thread_rng())
+    }
+
+    /// Same as `generate_keypair`, with all the randomness drawn from `rng`.
+    pub fn generate_keypair_from_rng(
+        prg: &mut impl Prg,
+        f: u8,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Self, Self) {
+        let n_bits = T::N * 8;
+        assert!(f > 0 && (f as usize) < n_bits);
+
+        // Random mask of the input.
+        let alpha: T = random_domain_element(rng);
+        let alpha_bits = bit_decomposition(alpha);
+
+        // Initialize seeds.
+        let s_a_wrap: u128 = rng.gen();
+        let s_b_wrap: u128 = rng.gen();
+        let s_a_low: u128 = rng.gen();
+        let s_b_low: u128 = rng.gen();
+
+        // Both trees share 1 on y < alpha, on the whole input and on its f lower bits.
+        let (one, zero) = (G::one(), G::zero());
+        let (cw_wrap, cw_leaf_wrap) =
+            generate_cw_from_bits(prg, &alpha_bits, one, zero, zero, s_a_wrap, s_b_wrap);
+        let (cw_low, cw_leaf_low) = generate_cw_from_bits(
+            prg,
+            &alpha_bits[n_bits - f as usize..],
+            one,
+            zero,
+            zero,
+            s_a_low,
+            s_b_low,
+        );
+
+        // Secret-share alpha and its higher bits between Alice and Bob.
+        let mask: T = random_domain_element(rng);
+        let high = G::from_block((alpha.to_u64() >> f) as u128);
+        let high_mask = G::from_block(rng.gen());
+
+        // Return a key pair.
+        (
+            GenericTruncKey {
+                alpha_share: wrapping_sub_domain(alpha, mask),
+                high_share: high.sub(high_mask),
+                f,
+                s_wrap: s_a_wrap,
+                cw_wrap: cw_wrap.clone(),
+                cw_leaf_wrap: cw_leaf_wrap.clone(),
+                s_low: s_a_low,
+                cw_low: cw_low.clone(),
+                cw_leaf_low: cw_leaf_low.clone(),
+            },
+            GenericTruncKey {
+                alpha_share: mask,
+                high_share: high_mask,
+                f,
+                s_wrap: s_b_wrap,
+                cw_wrap,
+                cw_leaf_wrap,
+                s_low: s_b_low,
+                cw_low,
+                cw_leaf_low,
+            },
+        )
+    }
+
+    /// Share of x >> f (logical shift), where x + alpha is the public input.
+    pub fn eval(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        assert!((party_id == 0u8) || (party_id == 1u8));
+        let n_bits = T::N * 8;
+        let f = self.f as usize;
+
+        let wrap: G = eval_path(
+            prg,
+            party_id,
+            self.s_wrap,
+            &self.cw_wrap,
+            &self.cw_leaf_wrap,
+            x,
+        );
+        // The borrow tree only reads the f lower bits of x.
+        let borrow: G = eval_path(
+            prg,
+            party_id,
+            self.s_low,
+            &self.cw_low,
+            &self.cw_leaf_low,
+            x,
+        );
+
+        let mut out = wrap
+            .mul(G::from_block(1u128 << (n_bits - f)))
+            .sub(borrow)
+            .sub(self.high_share);
+        if party_id == 1 {
+            out = out.add(G::from_block((x.to_u64() >> f) as u128));
+        }
+        out
+    }
+
+    /// Share of x >> f (arithmetic shift) for x in two's complement.
+    /// Shifting the public input by 2^(n - 1) maps x to the unsigned order, with the same mask.
+    pub fn eval_arithmetic(&self, prg: &mut impl Prg, party_id: u8, x: T) -> G {
+        let n_bits = T::N * 8;
+        let top = T::from_u64(1 << (n_bits - 1));
+        let out = self.eval(prg, party_id, wrapping_add_domain(x, top));
+        match party_id {
+            1 => out.sub(G::from_block(1u128 << (n_bits - 1 - self.f as usize))),
+            _ => out,
+        }
+    }
+}
+
+//
+// Serialization functions
+//
+
+fn write_key_to_array<T: Domain, G: Group>(key: &GenericTruncKey<T, G>, array: &mut [u8]) {
+    let n = T::N;
+    write_domain_element(key.alpha_share, &mut array[0..n]);
+    key.high_share.write_le_bytes(&mut array[n..n + G::BYTES]);
+    array[n + G::BYTES] = key.f;
+    let start = n + G::BYTES + 1;
+    let wrap_len = tree_len::<G>(n * 8);
+    write_tree_to_array(
+        key.s_wrap,
+        &key.cw_wrap,
+        &key.cw_leaf_wrap,
+        &mut array[start..start + wrap_len],
+    );
+    // The borrow tree has f levels, the rest of the line is padding.
+    let low = &mut array[start + wrap_len..];
+    let low_len = tree_len::<G>(key.cw_low.len());
+    write_tree_to_array(
+        key.s_low,
+        &key.cw_low,
+        &key.cw_leaf_low,
+        &mut low[..low_len],
+    );
+    low[low_len..].iter_mut().for_each(|byte| *byte = 0);
+}
+
+fn read_key_from_array<T: Domain, G: Group>(array: &[u8]) -> GenericTruncKey<T, G> {
+    let n = T::N;
+    let alpha_share = read_domain_element(&array[0..n]);
+    let high_share = G::read_le_bytes(&array[n..n + G::BYTES]);
+    let f = array[n + G::BYTES];
+    assert!(
+        f > 0 && (f as usize) < n * 8,
+        "Truncation key with 0 < f < {} expected, got {}",
+        n * 8,
+        f
+    );
+    let start = n + G::BYTES + 1;
+    let wrap_len = tree_len::<G>(n * 8);
+    let (s_wrap, cw_wrap, cw_leaf_wrap) =
+        read_tree_from_array(&array[start..start + wrap_len], n * 8);
+    let (s_low, cw_low, cw_leaf_low) = read_tree_from_array(&array[start + wrap_len..], f as usize);
+
+    GenericTruncKey {
+        alpha_share,
+        high_share,
+        f,
+        s_wrap,
+        cw_wrap,
+        cw_leaf_wrap,
+        s_low,
+        cw_low,
+        cw_leaf_low,
+    }
+}
@@ -1,39 +1,298 @@
 use aes::cipher::generic_array::GenericArray;
 use aes::Aes128;
 use aes::{BlockEncrypt, NewBlockCipher};
+use chacha20::cipher::{NewCipher, StreamCipher};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::convert::TryInto;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::BitXor;
 use std::slice;
 
 use super::stream::Prg;
 use super::L;
 
-pub fn share_leaf(mask_a: u32, mask_b: u32, share_bit: u8, flip_bit: u8) -> u32 {
-    let mut leaf = mask_b.wrapping_sub(mask_a).wrapping_add(share_bit as u32);
+/// Input domain of the DPF and DIF trees: unsigned integers on `N` bytes.
+/// The trees have one level per bit, i.e. `N * 8` levels.
+pub trait Domain: Copy + Debug + PartialEq + PartialOrd + Send + Sync {
+    const N: usize;
+
+    /// Embeds the value in a u64, without changing it.
+    fn to_u64(self) -> u64;
+
+    /// Keeps the `N * 8` least significant bits.
+    fn from_u64(x: u64) -> Self;
+}
+
+macro_rules! impl_domain {
+    ($t:ty, $n:expr) => {
+        impl Domain for $t {
+            const N: usize = $n;
+
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+
+            fn from_u64(x: u64) -> Self {
+                x as $t
+            }
+        }
+    };
+}
+
+impl_domain!(u8, 1);
+impl_domain!(u16, 2);
+impl_domain!(u32, 4);
+impl_domain!(u64, 8);
+
+/// Uniformly random element of the domain.
+pub fn random_domain_element<T: Domain>(rng: &mut impl rand::Rng) -> T {
+    T::from_u64(rng.gen())
+}
+
+/// Deterministic cryptographic randomness from a 128-bit seed, for reproducible keys.
+/// The seed fills the first half of the key of ChaCha20, which is pinned (unlike `StdRng`)
+/// so that the keys stay the same across versions of rand.
+pub fn seeded_rng(seed: u128) -> ChaCha20Rng {
+    let mut key = [0u8; 32];
+    key[0..16].copy_from_slice(&seed.to_le_bytes());
+    ChaCha20Rng::from_seed(key)
+}
+
+/// Addition modulo 2^(N * 8).
+pub fn wrapping_add_domain<T: Domain>(x: T, y: T) -> T {
+    T::from_u64(x.to_u64().wrapping_add(y.to_u64()))
+}
+
+/// Subtraction modulo 2^(N * 8).
+pub fn wrapping_sub_domain<T: Domain>(x: T, y: T) -> T {
+    T::from_u64(x.to_u64().wrapping_sub(y.to_u64()))
+}
+
+/// Little-endian serialization on exactly `T::N` bytes.
+pub fn write_domain_element<T: Domain>(x: T, out: &mut [u8]) {
+    out[0..T::N].copy_from_slice(&x.to_u64().to_le_bytes()[0..T::N]);
+}
+
+/// Reads `T::N` little-endian bytes.
+pub fn read_domain_element<T: Domain>(bytes: &[u8]) -> T {
+    let mut buffer = [0u8; 8];
+    buffer[0..T::N].copy_from_slice(&bytes[0..T::N]);
+    T::from_u64(u64::from_le_bytes(buffer))
+}
+
+/// Output group of the keys, where the leaves and the output shares live.
+pub trait Group: Copy + Debug + PartialEq + BitXor<Output = Self> + Send + Sync {
+    /// Size of an element in the raw lines.
+    const BYTES: usize;
+
+    fn zero() -> Self;
+
+    fn one() -> Self;
+
+    fn add(self, other: Self) -> Self;
+
+    fn neg(self) -> Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.add(other.neg())
+    }
+
+    /// Product with a public element, e.g. a table entry.
+    fn mul(self, other: Self) -> Self;
+
+    /// Keeps the least significant bits of some PRG output.
+    fn from_block(block: u128) -> Self;
+
+    /// Value written in the i64 results of the FFI.
+    fn to_i64(self) -> i64;
+
+    fn write_le_bytes(self, out: &mut [u8]);
+
+    fn read_le_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_ring {
+    ($t:ty, $n:expr) => {
+        impl Group for $t {
+            const BYTES: usize = $n;
+
+            fn zero() -> Self {
+                0
+            }
+
+            fn one() -> Self {
+                1
+            }
+
+            fn add(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+
+            fn neg(self) -> Self {
+                (0 as $t).wrapping_sub(self)
+            }
+
+            fn mul(self, other: Self) -> Self {
+                self.wrapping_mul(other)
+            }
+
+            fn from_block(block: u128) -> Self {
+                block as $t
+            }
+
+            fn to_i64(self) -> i64 {
+                self as i64
+            }
+
+            fn write_le_bytes(self, out: &mut [u8]) {
+                out[0..$n].copy_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le_bytes(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes[0..$n].try_into().unwrap())
+            }
+        }
+    };
+}
+
+// Z/2^32Z and Z/2^64Z
+impl_ring!(u32, 4);
+impl_ring!(u64, 8);
+
+// Z/2Z, i.e. XOR shares of a bit
+impl Group for bool {
+    const BYTES: usize = 1;
+
+    fn zero() -> Self {
+        false
+    }
+
+    fn one() -> Self {
+        true
+    }
+
+    fn add(self, other: Self) -> Self {
+        self ^ other
+    }
+
+    fn neg(self) -> Self {
+        self
+    }
+
+    fn mul(self, other: Self) -> Self {
+        self & other
+    }
+
+    fn from_block(block: u128) -> Self {
+        block & 1 == 1
+    }
+
+    fn to_i64(self) -> i64 {
+        self as i64
+    }
+
+    fn write_le_bytes(self, out: &mut [u8]) {
+        out[0] = self as u8;
+    }
+
+    fn read_le_bytes(bytes: &[u8]) -> Self {
+        bytes[0] & 1 == 1
+    }
+}
+
+/// Two elements shared together, e.g. to carry a vector payload in a single DIF tree.
+/// Only for groups of at most 4 bytes, so that a pair fits in half a PRG block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pair<G: Group>(pub G, pub G);
+
+impl<G: Group> BitXor for Pair<G> {
+    type Output = Self;
+
+    fn bitxor(self, other: Self) -> Self {
+        Pair(self.0 ^ other.0, self.1 ^ other.1)
+    }
+}
+
+impl<G: Group> Group for Pair<G> {
+    const BYTES: usize = 2 * G::BYTES;
+
+    fn zero() -> Self {
+        Pair(G::zero(), G::zero())
+    }
+
+    fn one() -> Self {
+        Pair(G::one(), G::one())
+    }
+
+    fn add(self, other: Self) -> Self {
+        Pair(self.0.add(other.0), self.1.add(other.1))
+    }
+
+    fn neg(self) -> Self {
+        Pair(self.0.neg(), self.1.neg())
+    }
+
+    fn mul(self, other: Self) -> Self {
+        Pair(self.0.mul(other.0), self.1.mul(other.1))
+    }
+
+    fn from_block(block: u128) -> Self {
+        const { assert!(G::BYTES <= 4, "Both halves must fit in 64 bits") };
+        Pair(G::from_block(block), G::from_block(block >> 32))
+    }
+
+    fn to_i64(self) -> i64 {
+        self.0.to_i64()
+    }
+
+    fn write_le_bytes(self, out: &mut [u8]) {
+        self.0.write_le_bytes(&mut out[0..G::BYTES]);
+        self.1.write_le_bytes(&mut out[G::BYTES..2 * G::BYTES]);
+    }
+
+    fn read_le_bytes(bytes: &[u8]) -> Self {
+        Pair(
+            G::read_le_bytes(&bytes[0..G::BYTES]),
+            G::read_le_bytes(&bytes[G::BYTES..2 * G::BYTES]),
+        )
+    }
+}
+
+pub fn share_leaf<G: Group>(mask_a: G, mask_b: G, value: G, flip_bit: u8) -> G {
+    let mut leaf = mask_b.sub(mask_a).add(value);
     if flip_bit == 1 {
-        leaf = 0u32.wrapping_sub(leaf);
+        leaf = leaf.neg();
     }
     leaf
 }
 
-pub fn compute_out(mask: u32, leaf: u32, tau: u8, flip_bit: u8) -> u32 {
-    let mut out: u32 = match tau {
-        1 => leaf.wrapping_add(mask),
+pub fn compute_out<G: Group>(mask: G, leaf: G, tau: u8, flip_bit: u8) -> G {
+    let mut out: G = match tau {
+        1 => leaf.add(mask),
         _ => mask,
     };
     if flip_bit == 1 {
-        out = 0u32.wrapping_sub(out);
+        out = out.neg();
     }
     out
 }
 
-pub fn bit_decomposition_u32(alpha: u32) -> Vec<u8> {
-    let mut alpha_bits: Vec<u8> = Vec::new();
+pub fn bit_decomposition<T: Domain>(alpha: T) -> Vec<u8> {
+    let alpha = alpha.to_u64();
+    let mut alpha_bits: Vec<u8> = Vec::with_capacity(T::N * 8);
     // Most significant bits first
-    for j in (0u8..32).rev() {
+    for j in (0..T::N * 8).rev() {
         alpha_bits.push((alpha >> j) as u8 & 1);
     }
     alpha_bits
 }
 
+pub fn bit_decomposition_u32(alpha: u32) -> Vec<u8> {
+    bit_decomposition(alpha)
+}
+
 /// # Safety
 /// De-referencing raw pointer
 pub unsafe fn write_aes_key_to_raw_line(aes_key: u128, key_line_pointer: *mut u8) {
@@ -55,56 +314,179 @@ pub unsafe fn read_aes_key_from_raw_line(key_line_pointer: *const u8) -> u128 {
     key
 }
 
-pub struct Mmo {
-    // pub expansion_factor: usize,
-    pub ciphers: Vec<Aes128>,
+/// Public AES keys for `Mmo` tied to a session: key i is AES_session_id(i).
+/// Both parties derive the same keys from the same session id.
+pub fn session_aes_keys(session_id: u128, n_aes_keys: usize) -> Vec<u128> {
+    let cipher = Aes128::new(GenericArray::from_slice(&session_id.to_le_bytes()));
+    (0..n_aes_keys)
+        .map(|i| {
+            let mut block = GenericArray::clone_from_slice(&(i as u128).to_le_bytes());
+            cipher.encrypt_block(&mut block);
+            u128::from_le_bytes(block.as_slice().try_into().unwrap())
+        })
+        .collect()
 }
 
-// TODO: hardcode the default keys
-impl Prg for Mmo {
-    fn from_slice(aes_keys: &[u128]) -> Mmo {
-        let mut ciphers = vec![];
-        for key in aes_keys {
-            ciphers.push(aes::Aes128::new(GenericArray::from_slice(
-                &key.to_le_bytes(),
-            )));
-        }
+/// Fingerprint of the public AES keys, recorded in the key headers to check that keygen
+/// and eval use the same keys: the low 64 bits of the XOR of AES_key_i(i) ^ i.
+pub fn aes_keys_fingerprint(aes_keys: &[u128]) -> u64 {
+    aes_keys.iter().enumerate().fold(0u128, |acc, (i, key)| {
+        let cipher = Aes128::new(GenericArray::from_slice(&key.to_le_bytes()));
+        let mut block = GenericArray::clone_from_slice(&(i as u128).to_le_bytes());
+        cipher.encrypt_block(&mut block);
+        acc ^ u128::from_le_bytes(block.as_slice().try_into().unwrap()) ^ (i as u128)
+    }) as u64
+}
+
+/// Matyas-Meyer-Oseas with K AES keys, i.e. K blocks per expansion.
+#[derive(Clone)]
+pub struct Mmo<const K: usize = 3> {
+    pub ciphers: [Aes128; K],
+}
+
+impl<const K: usize> Mmo<K> {
+    pub fn new(aes_keys: &[u128; K]) -> Self {
         Mmo {
-            // expansion_factor: ciphers.len(),
-            ciphers,
+            ciphers: std::array::from_fn(|i| {
+                Aes128::new(GenericArray::from_slice(&aes_keys[i].to_le_bytes()))
+            }),
         }
     }
+}
 
-    fn from_vec(aes_keys: &[u128]) -> Mmo {
-        let mut ciphers = vec![];
-        for key in aes_keys {
-            ciphers.push(aes::Aes128::new(GenericArray::from_slice(
-                &key.to_le_bytes(),
-            )));
-        }
-        Mmo {
-            // expansion_factor: ciphers.len(),
-            ciphers,
-        }
+// The first K keys, padded with zero keys.
+fn first_keys<const K: usize>(keys: &[u128]) -> [u128; K] {
+    std::array::from_fn(|i| keys.get(i).copied().unwrap_or(0))
+}
+
+// TODO: hardcode the default keys
+impl<const K: usize> Prg for Mmo<K> {
+    const EXPANSION: usize = K;
+
+    fn from_slice(aes_keys: &[u128]) -> Self {
+        Mmo::new(&first_keys(aes_keys))
+    }
+
+    fn from_vec(aes_keys: &[u128]) -> Self {
+        Self::from_slice(aes_keys)
     }
 
-    fn expand(&mut self, seed: u128) -> Vec<u128> {
-        // NOTE: to improve performance, try:
-        // - const generic instead of Vec?
-        // - inplace as much as possible, u8 rather than u128?
-        let mut output = Vec::new();
+    fn expand_into(&mut self, seed: u128, out: &mut [u128]) {
+        assert!(out.len() <= K);
         let mut output_array = [0u8; L];
         let seed_slice = seed.to_le_bytes();
         // Matyas-Meyer-Oseas with AES (ECB)
-        for cipher in &self.ciphers {
+        for (out, cipher) in out.iter_mut().zip(self.ciphers.iter()) {
             let mut block = GenericArray::clone_from_slice(&seed_slice);
             cipher.encrypt_block(&mut block);
             // XOR byte by byte
             for k in 0..L {
                 output_array[k] = block[k] ^ seed_slice[k];
             }
-            output.push(u128::from_le_bytes(output_array));
+            *out = u128::from_le_bytes(output_array);
+        }
+    }
+
+    /// Same output as `expand_array` on each seed. Each cipher encrypts several seeds at once,
+    /// so that the AES-NI backend of the `aes` crate pipelines the blocks.
+    fn expand_batch<const J: usize>(&mut self, seeds: &[u128], out: &mut [[u128; J]]) {
+        const { assert!(J <= K, "The Prg expands to fewer blocks") };
+        assert_eq!(seeds.len(), out.len());
+        let mut blocks = [GenericArray::default(); PAR_BLOCKS];
+        for (seeds, out) in seeds.chunks(PAR_BLOCKS).zip(out.chunks_mut(PAR_BLOCKS)) {
+            let blocks = &mut blocks[0..seeds.len()];
+            for (j, cipher) in self.ciphers[0..J].iter().enumerate() {
+                for (block, seed) in blocks.iter_mut().zip(seeds.iter()) {
+                    *block = GenericArray::from(seed.to_le_bytes());
+                }
+                cipher.encrypt_blocks(blocks);
+                for ((out, block), seed) in out.iter_mut().zip(blocks.iter()).zip(seeds.iter()) {
+                    out[j] = u128::from_le_bytes((*block).into()) ^ seed;
+                }
+            }
+        }
+    }
+}
+
+// Number of blocks given to the cipher at once, i.e. the AES-NI pipeline width.
+const PAR_BLOCKS: usize = 8;
+
+/// Prg from the ChaCha stream cipher, for platforms without AES acceleration.
+/// The 256-bit ChaCha key is the seed followed by a 128-bit public key that chains
+/// all the K public keys, and the expansion is the beginning of the keystream,
+/// i.e. one ChaCha block for up to 4 outputs. As with `Mmo`, there are K blocks per expansion.
+pub struct ChaChaPrg<C, const K: usize = 3> {
+    pub key: u128,
+    cipher: PhantomData<C>,
+}
+
+pub type ChaCha8Prg<const K: usize = 3> = ChaChaPrg<chacha20::ChaCha8, K>;
+pub type ChaCha12Prg<const K: usize = 3> = ChaChaPrg<chacha20::ChaCha12, K>;
+pub type ChaCha20Prg<const K: usize = 3> = ChaChaPrg<chacha20::ChaCha20, K>;
+
+impl<C: NewCipher + StreamCipher, const K: usize> ChaChaPrg<C, K> {
+    pub fn new(keys: &[u128; K]) -> Self {
+        assert!(K > 0);
+        // key_0 = keys[0], key_i = first block of ChaCha keyed by key_(i - 1) || keys[i].
+        let key = keys[1..]
+            .iter()
+            .fold(keys[0], |key, next| chacha_block::<C>(key, *next));
+        ChaChaPrg {
+            key,
+            cipher: PhantomData,
+        }
+    }
+}
+
+// First 128 bits of the keystream of ChaCha keyed by high || low, with a zero nonce.
+fn chacha_block<C: NewCipher + StreamCipher>(high: u128, low: u128) -> u128 {
+    let mut key = [0u8; 32];
+    key[0..L].copy_from_slice(&high.to_le_bytes());
+    key[L..].copy_from_slice(&low.to_le_bytes());
+    let mut cipher = C::new(GenericArray::from_slice(&key), &GenericArray::default());
+    let mut block = [0u8; L];
+    cipher.apply_keystream(&mut block);
+    u128::from_le_bytes(block)
+}
+
+// Not derived, the cipher itself does not need to be Clone.
+impl<C, const K: usize> Clone for ChaChaPrg<C, K> {
+    fn clone(&self) -> Self {
+        ChaChaPrg {
+            key: self.key,
+            cipher: PhantomData,
+        }
+    }
+}
+
+impl<C: NewCipher + StreamCipher, const K: usize> Prg for ChaChaPrg<C, K> {
+    const EXPANSION: usize = K;
+
+    fn from_slice(keys: &[u128]) -> Self {
+        ChaChaPrg::new(&first_keys(keys))
+    }
+
+    fn from_vec(keys: &[u128]) -> Self {
+        Self::from_slice(keys)
+    }
+
+    fn expand_into(&mut self, seed: u128, out: &mut [u128]) {
+        assert!(out.len() <= K);
+        let mut key = [0u8; 32];
+        key[0..L].copy_from_slice(&seed.to_le_bytes());
+        key[L..].copy_from_slice(&self.key.to_le_bytes());
+        let mut cipher = C::new(GenericArray::from_slice(&key), &GenericArray::default());
+        // One ChaCha block at a time, the keystream continues across blocks.
+        let mut bytes = [0u8; 4 * L];
+        for out in out.chunks_mut(4) {
+            let bytes = &mut bytes[0..out.len() * L];
+            for b in bytes.iter_mut() {
+                *b = 0;
+            }
+            cipher.apply_keystream(bytes);
+            for (out, block) in out.iter_mut().zip(bytes.chunks_exact(L)) {
+                *out = u128::from_le_bytes(block.try_into().unwrap());
+            }
         }
-        output
     }
 }
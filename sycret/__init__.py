@@ -1,2 +1,20 @@
 from sycret.fss import EqFactory  # noqa: 401
 from sycret.fss import LeFactory  # noqa: 401
+from sycret.fss import Eq64Factory  # noqa: 401
+from sycret.fss import Le64Factory  # noqa: 401
+from sycret.fss import EqXorFactory  # noqa: 401
+from sycret.fss import LeXorFactory  # noqa: 401
+from sycret.fss import EqAlphaFactory  # noqa: 401
+from sycret.fss import LeAlphaFactory  # noqa: 401
+from sycret.fss import IntervalFactory  # noqa: 401
+from sycret.fss import ReluFactory  # noqa: 401
+from sycret.fss import SignedLeFactory  # noqa: 401
+from sycret.fss import LtFactory  # noqa: 401
+from sycret.fss import GtFactory  # noqa: 401
+from sycret.fss import GeFactory  # noqa: 401
+from sycret.fss import TruncFactory  # noqa: 401
+from sycret.fss import MsbFactory  # noqa: 401
+from sycret.fss import MsbXorFactory  # noqa: 401
+from sycret.fss import BitDecompositionFactory  # noqa: 401
+from sycret.fss import BitDecompositionXorFactory  # noqa: 401
+from sycret.fss import MaxpoolFactory  # noqa: 401
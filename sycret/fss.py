@@ -1,11 +1,22 @@
 # third party
 import numpy as np
 
+from .sycret import ffi
 from .sycret import lib
 from .utils import _as_i64_array
+from .utils import _as_u32_array
+from .utils import _as_u64_array
 from .utils import _as_u8_array
 from .utils import _as_usize
 
+# Prgs of keygen_with_prg and eval_with_prg. Keys with a Prg or AES keys start with
+# a header of PRG_HEADER_LEN bytes: the Prg, then a fingerprint of the AES keys.
+PRG_MMO = 0
+PRG_CHACHA8 = 1
+PRG_CHACHA12 = 2
+PRG_CHACHA20 = 3
+PRG_HEADER_LEN = 1 + 8
+
 # TODO: add some utilities to interact with the keys,
 # (e.g. get alpha share for the tests)
 # No need for a class for the key (overhead)
@@ -27,6 +38,7 @@ class FSSFactory:
         lib_keygen=lib.keygen,
         lib_eval=lib.eval,
         op_id=1,
+        n_outputs=1,
     ):
         """Initializes some constants for FSS.
 
@@ -41,6 +53,7 @@ class FSSFactory:
             lib_keygen ([type], optional): [description]. Defaults to lib.keygen.
             lib_eval ([type], optional): [description]. Defaults to lib.eval.
             op_id (int, optional): [description]. Defaults to 1.
+            n_outputs (int, optional): Number of results per input. Defaults to 1.
         """
         # NOTE: these defaults work for both equality and comparison,
         # but new primitives can override them if necessary.
@@ -55,8 +68,94 @@ class FSSFactory:
         self.lib_keygen = lib_keygen
         self.lib_eval = lib_eval
         self.op_id = op_id
+        self.n_outputs = n_outputs
+        # Public AES keys of the Prg, None for the default keys.
+        self.aes_keys = None
+        # Prg recorded in the keys, None for Mmo.
+        self.prg_id = None
+        # Whether the keys start with the header of the Prg and its AES keys.
+        self.with_header = False
         return
 
+    def _use_header(self):
+        """Makes room for the header in the keys, once."""
+        if not self.with_header:
+            self.key_len += PRG_HEADER_LEN
+            self.with_header = True
+
+    def use_prg(self, prg_id):
+        """Sets the Prg used by both keygen and eval, e.g. PRG_CHACHA12.
+        The keys start with a header recording the Prg, so eval fails
+        on keys generated with another Prg.
+
+        Args:
+            prg_id: One of PRG_MMO, PRG_CHACHA8, PRG_CHACHA12, PRG_CHACHA20.
+        """
+        assert prg_id in (PRG_MMO, PRG_CHACHA8, PRG_CHACHA12, PRG_CHACHA20)
+        self._use_header()
+        self.prg_id = prg_id
+
+    def _aes_keys_args(self):
+        """The AES keys pointer and count, with a null pointer for the default keys."""
+        if self.aes_keys is None:
+            return (ffi.NULL, _as_usize(0))
+        return (_as_u8_array(self.aes_keys), _as_usize(self.aes_keys.shape[0]))
+
+    def use_aes_keys(self, aes_keys):
+        """Sets the public AES keys of the Prg, used by both keygen and eval.
+        The keys start with a header recording a fingerprint of the AES keys,
+        so eval fails on keys generated with other AES keys.
+
+        Args:
+            aes_keys: Array of lib.n_aes_keys(op_id) lines of 16 bytes.
+        """
+        aes_keys = np.ascontiguousarray(aes_keys, dtype=np.uint8)
+        assert aes_keys.shape == (lib.n_aes_keys(_as_usize(self.op_id)), 16)
+        self._use_header()
+        self.aes_keys = aes_keys
+
+    def use_session(self, session_id):
+        """Derives the public AES keys of the Prg from a session id.
+
+        Args:
+            session_id: 16 bytes shared by the dealer and both parties.
+        """
+        session = np.frombuffer(bytes(session_id), dtype=np.uint8)
+        assert session.shape == (16,)
+        n_aes_keys = lib.n_aes_keys(_as_usize(self.op_id))
+        aes_keys = np.zeros((n_aes_keys, 16), dtype=np.uint8)
+        lib.derive_aes_keys(
+            _as_u8_array(np.ascontiguousarray(session)),
+            _as_u8_array(aes_keys),
+            _as_usize(n_aes_keys),
+        )
+        self.use_aes_keys(aes_keys)
+
+    def _keygen(self, keys_a, keys_b, n_values, params=None):
+        """Calls Rust with the parameters and the AES keys, if any."""
+        r_args = (
+            _as_u8_array(keys_a),
+            _as_u8_array(keys_b),
+            _as_usize(n_values),
+            _as_usize(self.n_threads),
+            _as_usize(self.op_id),
+        )
+        if params is None and self.aes_keys is None and self.prg_id is None:
+            self.lib_keygen(*r_args)
+            return
+        if params is None:
+            params = np.zeros(0, dtype=np.uint64)
+        r_params = (_as_u64_array(params), _as_usize(params.shape[0]))
+        if self.prg_id is not None:
+            lib.keygen_with_prg(
+                *r_args, *r_params, *self._aes_keys_args(), _as_usize(self.prg_id)
+            )
+            return
+        if self.aes_keys is None:
+            self.lib_keygen(*r_args, *r_params)
+            return
+        lib.keygen_with_aes_keys(*r_args, *r_params, *self._aes_keys_args())
+
     def keygen(self, n_values=1):
         """[summary]
 
@@ -70,15 +169,8 @@ class FSSFactory:
         keys_a = np.zeros((n_values, self.key_len), dtype=self.key_type)
         keys_b = np.zeros((n_values, self.key_len), dtype=self.key_type)
 
-        # Convert types.
-        r_keys_a = _as_u8_array(keys_a)
-        r_keys_b = _as_u8_array(keys_b)
-        r_n_values = _as_usize(n_values)
-        r_n_threads = _as_usize(self.n_threads)
-        r_op_id = _as_usize(self.op_id)
-
         # Call Rust on this memory.
-        self.lib_keygen(r_keys_a, r_keys_b, r_n_values, r_n_threads, r_op_id)
+        self._keygen(keys_a, keys_b, n_values)
         return keys_a, keys_b
 
     def eval(self, party_id, xs, keys, n_threads=0):
@@ -94,7 +186,7 @@ class FSSFactory:
             [type]: [description]
         """
         n_values = xs.shape[0]
-        results = np.zeros(n_values, dtype=self.result_type)
+        results = np.zeros(n_values * self.n_outputs, dtype=self.result_type)
 
         # Warning: if the type is too big, the reshaping operation might be costly.
         np8_xs = np.ascontiguousarray(
@@ -109,10 +201,16 @@ class FSSFactory:
         r_n_threads = _as_usize(n_threads)
         r_op_id = _as_usize(self.op_id)
 
-        # Call Rust on this memory.
-        self.lib_eval(
-            r_party_id, r_xs, r_keys, r_results, r_n_values, r_n_threads, r_op_id
-        )
+        # Call Rust on this memory, with the same AES keys and Prg as keygen.
+        r_args = (r_party_id, r_xs, r_keys, r_results, r_n_values, r_n_threads, r_op_id)
+        if self.prg_id is not None:
+            lib.eval_with_prg(*r_args, *self._aes_keys_args(), _as_usize(self.prg_id))
+        elif self.aes_keys is None:
+            self.lib_eval(*r_args)
+        else:
+            lib.eval_with_aes_keys(*r_args, *self._aes_keys_args())
+        if self.n_outputs > 1:
+            return results.reshape(n_values, self.n_outputs)
         return results
 
     def alpha(self, keys_a: np.array, keys_b: np.array) -> np.array:
@@ -125,10 +223,12 @@ class FSSFactory:
         Returns:
             Alpha values in an array
         """
+        # The alpha share is after the header, if any.
+        start = PRG_HEADER_LEN if self.with_header else 0
         key_values = (
-            lambda self, key: key[0][0 : self.N]
+            lambda self, key: key[0][start : start + self.N]
             if key.shape[0] == 1
-            else np.ascontiguousarray(key[:, 0 : self.N])
+            else np.ascontiguousarray(key[:, start : start + self.N])
         )
 
         alpha_a = np.frombuffer(key_values(self, keys_a), dtype=np.uint32)
@@ -150,3 +250,291 @@ class LeFactory(FSSFactory):
 
     def __init__(self, n_threads=0):
         super().__init__(key_len=920, n_threads=n_threads, op_id=1)
+
+
+class Eq64Factory(FSSFactory):
+    """Distributed Point Function, with shares in Z/2^64Z."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=604, n_threads=n_threads, op_id=2)
+
+
+class Le64Factory(FSSFactory):
+    """Distributed Interval Function, with shares in Z/2^64Z."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=1180, n_threads=n_threads, op_id=3)
+
+
+class EqXorFactory(FSSFactory):
+    """Distributed Point Function, with XOR shares."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=597, n_threads=n_threads, op_id=4)
+
+
+class LeXorFactory(FSSFactory):
+    """Distributed Interval Function, with XOR shares."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=725, n_threads=n_threads, op_id=5)
+
+
+class ParamsFactory(FSSFactory):
+    """FSS keys with public parameters given at key generation."""
+
+    def __init__(self, params, key_len, n_threads=0, op_id=1):
+        super().__init__(
+            key_len=key_len,
+            n_threads=n_threads,
+            lib_keygen=lib.keygen_with_params,
+            op_id=op_id,
+        )
+        self.params = np.array(params, dtype=np.uint64)
+
+    def keygen(self, n_values=1):
+        """Generates keys for the parameters given at initialization.
+
+        Args:
+            n_values (int, optional): Number of keys. Defaults to 1.
+
+        Returns:
+            Tuple of arrays with the keys of each party.
+        """
+        keys_a = np.zeros((n_values, self.key_len), dtype=self.key_type)
+        keys_b = np.zeros((n_values, self.key_len), dtype=self.key_type)
+
+        # Call Rust with the parameters.
+        self._keygen(keys_a, keys_b, n_values, self.params)
+        return keys_a, keys_b
+
+
+class AlphaFactory(FSSFactory):
+    """FSS keys for alpha values and splits chosen by the dealer."""
+
+    def __init__(self, key_len, n_threads=0, op_id=18):
+        super().__init__(
+            key_len=key_len,
+            n_threads=n_threads,
+            lib_keygen=lib.keygen_with_params,
+            op_id=op_id,
+        )
+
+    def keygen(self, alpha, mask):
+        """Generates one key pair for each alpha.
+
+        Args:
+            alpha: Array of uint32 values where the keys are evaluated.
+            mask: Array of uint32 values, the share of alpha in keys_b.
+                The share in keys_a is alpha - mask.
+
+        Returns:
+            Tuple of arrays with the keys of each party.
+        """
+        alpha = np.asarray(alpha, dtype=np.uint64) & 0xFFFFFFFF
+        mask = np.asarray(mask, dtype=np.uint64) & 0xFFFFFFFF
+        assert alpha.shape == mask.shape
+        n_values = alpha.shape[0]
+        keys_a = np.zeros((n_values, self.key_len), dtype=self.key_type)
+        keys_b = np.zeros((n_values, self.key_len), dtype=self.key_type)
+
+        # [alpha_0, mask_0, alpha_1, mask_1, ...]
+        params = np.ascontiguousarray(np.stack([alpha, mask], axis=1).reshape(-1))
+        self._keygen(keys_a, keys_b, n_values, params)
+        return keys_a, keys_b
+
+
+class EqAlphaFactory(AlphaFactory):
+    """Distributed Point Function, for given alpha values."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=621, n_threads=n_threads, op_id=18)
+
+
+class LeAlphaFactory(AlphaFactory):
+    """Distributed Interval Function, for given alpha values."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=920, n_threads=n_threads, op_id=19)
+
+
+class IntervalFactory(ParamsFactory):
+    """Interval containment, for public bounds lower <= x <= upper."""
+
+    def __init__(self, lower, upper, n_threads=0):
+        super().__init__([lower, upper], key_len=932, n_threads=n_threads, op_id=6)
+
+
+class ReluFactory(FSSFactory):
+    """ReLU on signed 32-bit inputs, returning shares of max(x, 0)."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=1152, n_threads=n_threads, op_id=7)
+
+
+class SignedLeFactory(FSSFactory):
+    """Distributed Interval Function, comparing two's complement inputs."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=920, n_threads=n_threads, op_id=8)
+
+
+class LtFactory(FSSFactory):
+    """Distributed Interval Function for x < alpha."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=920, n_threads=n_threads, op_id=9)
+
+
+class GtFactory(FSSFactory):
+    """Distributed Interval Function for x > alpha."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=920, n_threads=n_threads, op_id=10)
+
+
+class GeFactory(FSSFactory):
+    """Distributed Interval Function for x >= alpha."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=920, n_threads=n_threads, op_id=11)
+
+
+class TruncFactory(ParamsFactory):
+    """Truncation by f bits, with a logical or an arithmetic shift."""
+
+    def __init__(self, f, signed=False, n_threads=0):
+        op_id = 13 if signed else 12
+        super().__init__([f], key_len=1813, n_threads=n_threads, op_id=op_id)
+
+
+class MsbFactory(FSSFactory):
+    """Most significant bit extraction."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=896, n_threads=n_threads, op_id=14)
+
+
+class MsbXorFactory(FSSFactory):
+    """Most significant bit extraction, with XOR shares."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=704, n_threads=n_threads, op_id=15)
+
+
+class BitDecompositionFactory(FSSFactory):
+    """Bit decomposition, with one share per bit (least significant first)."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=14660, n_threads=n_threads, op_id=16, n_outputs=32)
+
+
+class BitDecompositionXorFactory(FSSFactory):
+    """Bit decomposition, with one XOR share per bit (least significant first)."""
+
+    def __init__(self, n_threads=0):
+        super().__init__(key_len=11492, n_threads=n_threads, op_id=17, n_outputs=32)
+
+
+class MaxpoolFactory:
+    """Maxpool and one-hot argmax over windows of k shares in Z/2^32Z,
+    with the whole tournament in Rust."""
+
+    def __init__(self, k):
+        self.k = k
+        # The comparisons use the MSB keys and AES keys of this op.
+        self.op_id = 14
+        self.material_len = lib.maxpool_material_len(_as_usize(k))
+        # Public AES keys of the comparisons, None for the default keys.
+        self.aes_keys = None
+
+    def use_aes_keys(self, aes_keys):
+        """Sets the public AES keys of the comparisons, used by both keygen and eval.
+
+        Args:
+            aes_keys: Array of lib.n_aes_keys(op_id) lines of 16 bytes.
+        """
+        aes_keys = np.ascontiguousarray(aes_keys, dtype=np.uint8)
+        assert aes_keys.shape == (lib.n_aes_keys(_as_usize(self.op_id)), 16)
+        self.aes_keys = aes_keys
+
+    def _aes_keys_args(self):
+        """The AES keys pointer and count, with a null pointer for the default keys."""
+        if self.aes_keys is None:
+            return (ffi.NULL, _as_usize(0))
+        return (_as_u8_array(self.aes_keys), _as_usize(self.aes_keys.shape[0]))
+
+    def keygen(self, n_windows=1):
+        """Generates the material of both parties: comparison keys and Beaver triples.
+
+        Args:
+            n_windows (int, optional): Number of windows. Defaults to 1.
+
+        Returns:
+            Tuple of arrays with one line of material per window for each party.
+        """
+        materials_a = np.zeros((n_windows, self.material_len), dtype=np.uint8)
+        materials_b = np.zeros((n_windows, self.material_len), dtype=np.uint8)
+        lib.maxpool_keygen(
+            _as_u8_array(materials_a),
+            _as_u8_array(materials_b),
+            _as_usize(n_windows),
+            _as_usize(self.k),
+            *self._aes_keys_args(),
+        )
+        return materials_a, materials_b
+
+    def eval(self, party_id, xs, materials, exchange):
+        """Shares of the max and of the one-hot argmax of each window.
+
+        Args:
+            party_id: 0 or 1.
+            xs: Array of shares of shape (n_windows, k), cast to uint32.
+            materials: Material of this party from keygen, one line per window.
+            exchange: Function sending a uint32 array of our shares to the other party
+                and returning its shares, in the same order. It is called twice per
+                level of the tournament, for all the windows at once.
+
+        Returns:
+            Tuple of the shares of the max, of shape (n_windows,),
+            and of the one-hot argmax, of shape (n_windows, k).
+        """
+        xs = np.ascontiguousarray(xs, dtype=np.uint32)
+        n_windows = xs.shape[0]
+        assert xs.shape == (n_windows, self.k)
+        materials = np.ascontiguousarray(materials, dtype=np.uint8)
+        assert materials.shape == (n_windows, self.material_len)
+        max_shares = np.zeros(n_windows, dtype=np.uint32)
+        argmax_shares = np.zeros((n_windows, self.k), dtype=np.uint32)
+
+        # Exceptions cannot cross Rust, keep the first one and raise it after the call.
+        errors = []
+
+        @ffi.callback("void(uint32_t *, uint32_t *, unsigned long)")
+        def r_exchange(shares_p, other_shares_p, n_shares):
+            if errors:
+                return
+            try:
+                shares = np.frombuffer(
+                    ffi.buffer(shares_p, 4 * n_shares), dtype=np.uint32
+                ).copy()
+                other = np.ascontiguousarray(exchange(shares), dtype=np.uint32)
+                assert other.shape == (n_shares,)
+                ffi.memmove(other_shares_p, other.tobytes(), 4 * n_shares)
+            except Exception as e:
+                errors.append(e)
+
+        lib.maxpool_eval(
+            _as_usize(party_id),
+            _as_u32_array(xs),
+            _as_u8_array(materials),
+            _as_u32_array(max_shares),
+            _as_u32_array(argmax_shares),
+            _as_usize(n_windows),
+            _as_usize(self.k),
+            *self._aes_keys_args(),
+            r_exchange,
+        )
+        if errors:
+            raise errors[0]
+        return max_shares, argmax_shares
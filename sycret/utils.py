@@ -19,3 +19,7 @@ def _as_usize(np_int):
 
 def _as_u8_array(np_u8_array):
     return ffi.cast("uint8_t *", np_u8_array.ctypes.data)
+
+
+def _as_u32_array(np_uint32_array):
+    return ffi.cast("uint32_t *", np_uint32_array.ctypes.data)
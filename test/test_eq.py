@@ -66,6 +66,31 @@ def test_multiline(n_values, n_loops=16):
         assert (result == expected_result).all()
 
 
+
+def test_simple_raw_eq64():
+
+    eq = sycret.Eq64Factory(n_threads=6)
+
+    for _ in range(16):
+        keys_a, keys_b = eq.keygen(1)
+        alpha = eq.alpha(keys_a, keys_b)
+
+        x = alpha.astype(np.int64)
+
+        # The shares wrap around in Z/2^64Z, like the int64 numpy arrays.
+        r_a, r_b = (
+            eq.eval(0, x, keys_a),
+            eq.eval(1, x, keys_b),
+        )
+        assert r_a + r_b == 1
+
+        x = alpha.astype(np.int64) + 31
+        r_a, r_b = (
+            eq.eval(0, x, keys_a),
+            eq.eval(1, x, keys_b),
+        )
+        assert r_a + r_b == 0
+
 if __name__ == "__main__":
     parser = argparse.ArgumentParser()
     parser.add_argument("--N", type=int, default=500_000, required=False)
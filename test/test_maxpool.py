@@ -0,0 +1,49 @@
+# stdlib
+import queue
+import threading
+
+# third party
+import numpy as np
+import pytest
+
+import sycret
+
+
+@pytest.mark.parametrize("k", [1, 2, 4, 9])
+def test_maxpool_with_exchange(k, n_windows=8):
+
+    maxpool = sycret.MaxpoolFactory(k)
+    materials_a, materials_b = maxpool.keygen(n_windows)
+
+    # Distinct small signed values, so that the argmax is unique.
+    xs = np.random.permutation(np.arange(-1000, 1000))[: n_windows * k]
+    xs = xs.reshape(n_windows, k).astype(np.int64)
+    xs_a = np.random.randint(0, 2 ** 32, size=xs.shape, dtype=np.uint64)
+    xs_b = (xs - xs_a.astype(np.int64)) % (2 ** 32)
+
+    # Each party runs on its own thread, with queues for the exchanges.
+    to_a, to_b = queue.Queue(), queue.Queue()
+    results = {}
+
+    def party(party_id, xs, materials, send, receive):
+        def exchange(shares):
+            send.put(shares)
+            return receive.get()
+
+        results[party_id] = maxpool.eval(party_id, xs, materials, exchange)
+
+    threads = [
+        threading.Thread(target=party, args=(0, xs_a, materials_a, to_b, to_a)),
+        threading.Thread(target=party, args=(1, xs_b, materials_b, to_a, to_b)),
+    ]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    (max_a, argmax_a), (max_b, argmax_b) = results[0], results[1]
+    max_values = (max_a + max_b).astype(np.int32)
+    assert (max_values == xs.max(axis=1)).all()
+    one_hot = argmax_a + argmax_b
+    assert (one_hot.argmax(axis=1) == xs.argmax(axis=1)).all()
+    assert (one_hot.sum(axis=1) == 1).all()
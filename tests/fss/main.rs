@@ -0,0 +1,5 @@
+mod test_dcf;
+mod test_dif;
+mod test_dpf;
+mod test_halftree;
+mod test_mpdpf;
@@ -0,0 +1,60 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::fss::dcf::*;
+use sycret::fss::dif::DIFKeyBeta;
+use sycret::utils::{Mmo, Pair};
+
+#[test]
+fn generate_and_evaluate_dcf() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Include the edges of the domain.
+    let mut alphas: Vec<u8> = vec![0, 1, 255];
+    alphas.extend((0..8).map(|_| rng.gen::<u8>()));
+    for &alpha in alphas.iter() {
+        let beta: u64 = rng.gen();
+        let (k_a, k_b) = DCFKey::generate_keypair(&mut prg, alpha, beta);
+        let (x_a, x_b) = DCFKey::<u8, bool>::generate_keypair(&mut prg, alpha, true);
+        for x in 0..=255u8 {
+            let out = k_a
+                .eval(&mut prg, 0, x)
+                .wrapping_add(k_b.eval(&mut prg, 1, x));
+            assert_eq!(out, if x < alpha { beta } else { 0 });
+
+            let out = x_a.eval(&mut prg, 0, x) ^ x_b.eval(&mut prg, 1, x);
+            assert_eq!(out, x < alpha);
+        }
+    }
+}
+
+#[test]
+fn evaluate_dcf_with_vector_payload() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    let alpha: u32 = rng.gen();
+    let beta: Pair<u32> = Pair(rng.gen(), rng.gen());
+    let (k_a, k_b) = DCFKey::generate_keypair(&mut prg, alpha, beta);
+
+    // The batched evaluations match the point-wise evaluation.
+    let start = alpha.saturating_sub(500).min(u32::MAX - 1000);
+    let mut out_a = vec![Pair(0u32, 0u32); 1000];
+    let mut out_b = vec![Pair(0u32, 0u32); 1000];
+    k_a.eval_range(&prg, 0, start, &mut out_a);
+    k_b.eval_range(&prg, 1, start, &mut out_b);
+    let xs: Vec<u32> = (0..1000).map(|j| start + j).collect();
+    let points_a = k_a.eval_points(&mut prg, 0, &xs);
+    for (j, &x) in xs.iter().enumerate() {
+        assert_eq!(out_a[j], k_a.eval(&mut prg, 0, x));
+        assert_eq!(points_a[j], out_a[j]);
+        let out = Pair(
+            out_a[j].0.wrapping_add(out_b[j].0),
+            out_a[j].1.wrapping_add(out_b[j].1),
+        );
+        assert_eq!(out, if x < alpha { beta } else { Pair(0, 0) });
+    }
+}
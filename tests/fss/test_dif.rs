@@ -2,7 +2,6 @@ use rand::Rng;
 
 extern crate sycret;
 use sycret::fss::dif::*;
-use sycret::stream::{FSSKey, Prg};
 use sycret::utils::Mmo;
 
 #[test]
@@ -12,12 +11,12 @@ fn generate_and_evaluate_alpha() {
     for _ in 0..16 {
         let alpha: u32 = rng.gen();
         let aes_keys: [u128; 4] = rng.gen();
-        let mut prg = Mmo::from_slice(&aes_keys);
+        let mut prg = Mmo::new(&aes_keys);
         let (k_a, k_b) = DIFKeyAlpha1::generate_keypair(&mut prg, alpha);
 
         // Evaluate separately on the same input.
-        let t_a_output = k_a.eval(&mut prg, 0, alpha);
-        let t_b_output = k_b.eval(&mut prg, 1, alpha);
+        let t_a_output: u32 = k_a.eval(&mut prg, 0, alpha);
+        let t_b_output: u32 = k_b.eval(&mut prg, 1, alpha);
 
         // The output bit is additively secret-shared in Z/32Z
         assert_eq!(t_a_output.wrapping_add(t_b_output), 1u32);
@@ -31,40 +30,235 @@ fn generate_and_evaluate_le_alpha() {
     for _ in 0..16 {
         let alpha: u32 = rng.gen();
         let aes_keys: [u128; 4] = rng.gen();
-        let mut prg = Mmo::from_slice(&aes_keys);
+        let mut prg = Mmo::new(&aes_keys);
         let (k_a, k_b) = DIFKeyAlpha1::generate_keypair(&mut prg, alpha);
 
-        let not_alpha: u32 = alpha.wrapping_sub(rng.gen::<u32>());
+        let mut not_alpha: u32 = alpha.wrapping_sub(rng.gen::<u32>());
         if not_alpha > alpha {
-            let not_alpha = alpha.wrapping_sub(1);
+            not_alpha = alpha.wrapping_sub(1);
         }
         // Evaluate separately on the same input
-        let t_a_output = k_a.eval(&mut prg, 0, not_alpha);
-        let t_b_output = k_b.eval(&mut prg, 1, not_alpha);
+        let t_a_output: u32 = k_a.eval(&mut prg, 0, not_alpha);
+        let t_b_output: u32 = k_b.eval(&mut prg, 1, not_alpha);
 
         // The output bit is additively secret-shared in Z/32Z
-        assert_eq!(t_a_output.wrapping_add(t_b_output), 0u32);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), 1u32);
     }
 }
 
+#[test]
 fn generate_and_evaluate_strictly_greater_than_alpha() {
     // alpha is randomized, test on different inputs to make sure we are not just lucky.
     let mut rng = rand::thread_rng();
     for _ in 0..16 {
         let alpha: u32 = rng.gen();
         let aes_keys: [u128; 4] = rng.gen();
-        let mut prg = Mmo::from_slice(&aes_keys);
+        let mut prg = Mmo::new(&aes_keys);
         let (k_a, k_b) = DIFKeyAlpha1::generate_keypair(&mut prg, alpha);
 
-        let not_alpha: u32 = alpha.wrapping_add(rng.gen::<u32>());
+        let mut not_alpha: u32 = alpha.wrapping_add(rng.gen::<u32>());
         if not_alpha <= alpha {
-            let not_alpha = alpha.wrapping_add(1);
+            not_alpha = alpha.wrapping_add(1);
         }
         // Evaluate separately on the same input
-        let t_a_output = k_a.eval(&mut prg, 0, not_alpha);
-        let t_b_output = k_b.eval(&mut prg, 1, not_alpha);
+        let t_a_output: u32 = k_a.eval(&mut prg, 0, not_alpha);
+        let t_b_output: u32 = k_b.eval(&mut prg, 1, not_alpha);
 
         // The output bit is additively secret-shared in Z/32Z
         assert_eq!(t_a_output.wrapping_add(t_b_output), 0u32);
     }
 }
+
+#[test]
+fn generate_and_evaluate_other_domains() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        // The whole 8 bits domain is small enough to be checked exhaustively.
+        let alpha: u8 = rng.gen();
+        let (k_a, k_b) = DIFKeyAlpha1::generate_keypair(&mut prg, alpha);
+        assert_eq!(k_a.cw_leaf.len(), 8 + 1);
+        for x in 0..=255u8 {
+            let t_a_output: u32 = k_a.eval(&mut prg, 0, x);
+            let t_b_output: u32 = k_b.eval(&mut prg, 1, x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (x <= alpha) as u32);
+        }
+
+        let alpha: u16 = rng.gen();
+        let (k_a, k_b) = DIFKeyAlpha1::generate_keypair(&mut prg, alpha);
+        for x in [rng.gen(), alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output: u32 = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: u32 = k_b.eval(&mut prg, 1, *x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x <= alpha) as u32);
+        }
+
+        let alpha: u64 = rng.gen();
+        let (k_a, k_b) = DIFKeyAlpha1::generate_keypair(&mut prg, alpha);
+        for x in [rng.gen(), alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output: u32 = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: u32 = k_b.eval(&mut prg, 1, *x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x <= alpha) as u32);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_64_bits_output() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u16 = rng.gen();
+        let (k_a, k_b) = DIFKeyAlpha1::<u16, u64>::generate_keypair(&mut prg, alpha);
+        for x in [rng.gen(), alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output: u64 = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: u64 = k_b.eval(&mut prg, 1, *x);
+
+            // The output bit is additively secret-shared in Z/2^64Z
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x <= alpha) as u64);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_xor_output() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u8 = rng.gen();
+        let (k_a, k_b) = DIFKeyAlpha1::<u8, bool>::generate_keypair(&mut prg, alpha);
+        for x in 0..=255u8 {
+            let t_a_output: bool = k_a.eval(&mut prg, 0, x);
+            let t_b_output: bool = k_b.eval(&mut prg, 1, x);
+
+            // The output bit is XOR secret-shared
+            assert_eq!(t_a_output ^ t_b_output, x <= alpha);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_beta() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u8 = rng.gen();
+        let beta: u32 = rng.gen();
+        let (k_a, k_b) = DIFKeyAlphaBeta::generate_keypair(&mut prg, alpha, beta);
+        for x in 0..=255u8 {
+            let t_a_output = k_a.eval(&mut prg, 0, x);
+            let t_b_output = k_b.eval(&mut prg, 1, x);
+
+            // Shares of beta on x <= alpha, of 0 elsewhere
+            let expected = if x <= alpha { beta } else { 0 };
+            assert_eq!(t_a_output.wrapping_add(t_b_output), expected);
+        }
+    }
+}
+
+#[test]
+fn evaluate_all_and_range() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Full domain on 16 bits, i.e. a whole CDF.
+    let alpha: u16 = rng.gen();
+    let (k_a, k_b) = DIFKeyAlpha1::<u16, u64>::generate_keypair(&mut prg, alpha);
+    let mut out_a = vec![0u64; 1 << 16];
+    let mut out_b = vec![0u64; 1 << 16];
+    k_a.eval_all(&prg, 0, &mut out_a);
+    k_b.eval_all(&prg, 1, &mut out_b);
+    for x in 0..(1 << 16) {
+        assert_eq!(
+            out_a[x].wrapping_add(out_b[x]),
+            (x <= alpha as usize) as u64
+        );
+    }
+
+    // The range evaluation matches the point-wise evaluation, even when it is not aligned.
+    let alpha: u32 = rng.gen();
+    let beta: u32 = rng.gen();
+    let (k_a, k_b) = DIFKeyAlphaBeta::generate_keypair(&mut prg, alpha, beta);
+    let start = alpha.saturating_sub(1000).min(u32::MAX - 2021);
+    let mut out_a = vec![0u32; 2021];
+    let mut out_b = vec![0u32; 2021];
+    k_a.eval_range(&prg, 0, start, &mut out_a);
+    k_b.eval_range(&prg, 1, start, &mut out_b);
+    for j in 0..2021 {
+        let x = start + j as u32;
+        assert_eq!(out_a[j], k_a.eval(&mut prg, 0, x));
+        let expected = if x <= alpha { beta } else { 0 };
+        assert_eq!(out_a[j].wrapping_add(out_b[j]), expected);
+    }
+}
+
+#[test]
+#[should_panic(expected = "The tree must have one level per bit of the domain")]
+fn evaluate_range_of_a_shorter_tree() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // A tree on 31 bits, evaluated as a tree on u32.
+    let alpha_bits: Vec<u8> = (0..31).map(|_| rng.gen_range(0, 2)).collect();
+    let (cw, cw_leaf) =
+        generate_cw_from_bits(&mut prg, &alpha_bits, 1u32, 0, 0, rng.gen(), rng.gen());
+    let mut out = vec![0u32; 16];
+    eval_range_into(&prg, 0, rng.gen(), &cw, &cw_leaf, 0u32, &mut out);
+}
+
+#[test]
+fn evaluate_points_in_one_walk() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    let alpha: u32 = rng.gen();
+    let (k_a, _) = DIFKeyAlpha1::<u32>::generate_keypair(&mut prg, alpha);
+
+    // Unsorted points, with duplicates and points around alpha.
+    let mut xs: Vec<u32> = (0..32).map(|_| rng.gen()).collect();
+    xs.extend_from_slice(&[
+        alpha,
+        alpha.wrapping_add(1),
+        alpha.wrapping_sub(1),
+        alpha,
+        0,
+    ]);
+    let out: Vec<u32> = eval_points(&mut prg, 0, k_a.s, &k_a.cw, &k_a.cw_leaf, &xs);
+    for (i, &x) in xs.iter().enumerate() {
+        assert_eq!(out[i], k_a.eval(&mut prg, 0, x));
+    }
+}
+
+#[test]
+fn generate_and_evaluate_other_directions() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Include the edges of the domain, where offset tricks wrap around.
+    let mut alphas: Vec<u8> = vec![0, 1, 254, 255];
+    alphas.extend((0..8).map(|_| rng.gen::<u8>()));
+    for &alpha in alphas.iter() {
+        let (lt_a, lt_b) = DIFKeyLt::<u8>::generate_keypair(&mut prg, alpha);
+        let (gt_a, gt_b) = DIFKeyGt::<u8>::generate_keypair(&mut prg, alpha);
+        let (ge_a, ge_b) = DIFKeyGe::<u8, bool>::generate_keypair(&mut prg, alpha);
+        for x in 0..=255u8 {
+            let lt: u32 = lt_a
+                .eval(&mut prg, 0, x)
+                .wrapping_add(lt_b.eval(&mut prg, 1, x));
+            let gt: u32 = gt_a
+                .eval(&mut prg, 0, x)
+                .wrapping_add(gt_b.eval(&mut prg, 1, x));
+            let ge = ge_a.eval(&mut prg, 0, x) ^ ge_b.eval(&mut prg, 1, x);
+            assert_eq!(lt, (x < alpha) as u32);
+            assert_eq!(gt, (x > alpha) as u32);
+            assert_eq!(ge, x >= alpha);
+        }
+    }
+}
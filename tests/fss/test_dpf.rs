@@ -2,8 +2,7 @@ use rand::Rng;
 
 extern crate sycret;
 use sycret::fss::dpf::*;
-use sycret::stream::{FSSKey, Prg};
-use sycret::utils::Mmo;
+use sycret::utils::{seeded_rng, Mmo};
 
 #[test]
 fn generate_and_evaluate_alpha() {
@@ -12,12 +11,12 @@ fn generate_and_evaluate_alpha() {
     for _ in 0..16 {
         let alpha: u32 = rng.gen();
         let aes_keys: [u128; 4] = rng.gen();
-        let mut prg = Mmo::from_slice(&aes_keys);
+        let mut prg = Mmo::new(&aes_keys);
         let (k_a, k_b) = DPFKeyAlpha1::generate_keypair(&mut prg, alpha);
 
         // Evaluate separately on the same input.
-        let t_a_output = k_a.eval(&mut prg, 0, alpha);
-        let t_b_output = k_b.eval(&mut prg, 1, alpha);
+        let t_a_output: u32 = k_a.eval(&mut prg, 0, alpha);
+        let t_b_output: u32 = k_b.eval(&mut prg, 1, alpha);
 
         // The output bit is additively secret-shared in Z/32Z
         assert_eq!(t_a_output.wrapping_add(t_b_output), 1u32);
@@ -31,18 +30,197 @@ fn generate_and_evaluate_not_alpha() {
     for _ in 0..16 {
         let alpha: u32 = rng.gen();
         let aes_keys: [u128; 4] = rng.gen();
-        let mut prg = Mmo::from_slice(&aes_keys);
+        let mut prg = Mmo::new(&aes_keys);
         let (k_a, k_b) = DPFKeyAlpha1::generate_keypair(&mut prg, alpha);
 
-        let not_alpha: u32 = alpha.wrapping_add(rng.gen::<u32>());
+        let mut not_alpha: u32 = alpha.wrapping_add(rng.gen::<u32>());
         if not_alpha == alpha {
-            let not_alpha = alpha.wrapping_add(1);
+            not_alpha = alpha.wrapping_add(1);
         }
         // Evaluate separately on the same input
-        let t_a_output = k_a.eval(&mut prg, 0, not_alpha);
-        let t_b_output = k_b.eval(&mut prg, 1, not_alpha);
+        let t_a_output: u32 = k_a.eval(&mut prg, 0, not_alpha);
+        let t_b_output: u32 = k_b.eval(&mut prg, 1, not_alpha);
 
         // The output bit is additively secret-shared in Z/32Z
         assert_eq!(t_a_output.wrapping_add(t_b_output), 0u32);
     }
 }
+
+#[test]
+fn generate_and_evaluate_other_domains() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u8 = rng.gen();
+        let (k_a, k_b) = DPFKeyAlpha1::generate_keypair(&mut prg, alpha);
+        assert_eq!(k_a.cw.len(), 8);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output: u32 = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: u32 = k_b.eval(&mut prg, 1, *x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x == alpha) as u32);
+        }
+
+        let alpha: u16 = rng.gen();
+        let (k_a, k_b) = DPFKeyAlpha1::generate_keypair(&mut prg, alpha);
+        assert_eq!(k_a.cw.len(), 16);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output: u32 = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: u32 = k_b.eval(&mut prg, 1, *x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x == alpha) as u32);
+        }
+
+        let alpha: u64 = rng.gen();
+        let (k_a, k_b) = DPFKeyAlpha1::generate_keypair(&mut prg, alpha);
+        assert_eq!(k_a.cw.len(), 64);
+        for x in [alpha, alpha ^ (1 << 63)].iter() {
+            let t_a_output: u32 = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: u32 = k_b.eval(&mut prg, 1, *x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x == alpha) as u32);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_64_bits_output() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u32 = rng.gen();
+        let (k_a, k_b) = DPFKeyAlpha1::<u32, u64>::generate_keypair(&mut prg, alpha);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output: u64 = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: u64 = k_b.eval(&mut prg, 1, *x);
+
+            // The output bit is additively secret-shared in Z/2^64Z
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x == alpha) as u64);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_xor_output() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u32 = rng.gen();
+        let (k_a, k_b) = DPFKeyAlpha1::<u32, bool>::generate_keypair(&mut prg, alpha);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output: bool = k_a.eval(&mut prg, 0, *x);
+            let t_b_output: bool = k_b.eval(&mut prg, 1, *x);
+
+            // The output bit is XOR secret-shared
+            assert_eq!(t_a_output ^ t_b_output, *x == alpha);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_beta() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u32 = rng.gen();
+        let beta: u64 = rng.gen();
+        let (k_a, k_b) = DPFKeyAlphaBeta::generate_keypair(&mut prg, alpha, beta);
+
+        // Shares of beta on alpha
+        let t_a_output = k_a.eval(&mut prg, 0, alpha);
+        let t_b_output = k_b.eval(&mut prg, 1, alpha);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), beta);
+
+        // Shares of 0 elsewhere
+        let not_alpha = alpha ^ (1 << rng.gen_range(0, 32));
+        let t_a_output = k_a.eval(&mut prg, 0, not_alpha);
+        let t_b_output = k_b.eval(&mut prg, 1, not_alpha);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), 0u64);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_vector_beta() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for &length in [1, 3, 64].iter() {
+        let alpha: u16 = rng.gen();
+        let beta: Vec<u32> = (0..length).map(|_| rng.gen()).collect();
+        let (k_a, k_b) = DPFKeyAlphaVector::generate_keypair(&mut prg, alpha, &beta);
+
+        // Only the leaf correction word depends on the length.
+        assert_eq!(k_a.cw.len(), 16);
+        assert_eq!(k_a.cw_leaf.len(), length);
+
+        // Shares of beta on alpha
+        let t_a_output = k_a.eval(&mut prg, 0, alpha);
+        let t_b_output = k_b.eval(&mut prg, 1, alpha);
+        for j in 0..length {
+            assert_eq!(t_a_output[j].wrapping_add(t_b_output[j]), beta[j]);
+        }
+
+        // Shares of the zero vector elsewhere
+        let t_a_output = k_a.eval(&mut prg, 0, alpha.wrapping_add(1));
+        let t_b_output = k_b.eval(&mut prg, 1, alpha.wrapping_add(1));
+        for j in 0..length {
+            assert_eq!(t_a_output[j].wrapping_add(t_b_output[j]), 0);
+        }
+    }
+}
+
+#[test]
+fn evaluate_all_and_range() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Full domain on 16 bits, with shares of beta.
+    let alpha: u16 = rng.gen();
+    let beta: u64 = rng.gen();
+    let (k_a, k_b) = DPFKeyAlphaBeta::generate_keypair(&mut prg, alpha, beta);
+    let mut out_a = vec![0u64; 1 << 16];
+    let mut out_b = vec![0u64; 1 << 16];
+    k_a.eval_all(&prg, 0, &mut out_a);
+    k_b.eval_all(&prg, 1, &mut out_b);
+    for x in 0..(1 << 16) {
+        let expected = if x == alpha as usize { beta } else { 0 };
+        assert_eq!(out_a[x].wrapping_add(out_b[x]), expected);
+    }
+
+    // The range evaluation matches the point-wise evaluation, even when it is not aligned.
+    let alpha: u32 = rng.gen();
+    let (k_a, k_b) = DPFKeyAlpha1::<u32, bool>::generate_keypair(&mut prg, alpha);
+    let start = alpha.saturating_sub(1000).min(u32::MAX - 2021);
+    let mut out_a = vec![false; 2021];
+    let mut out_b = vec![false; 2021];
+    k_a.eval_range(&prg, 0, start, &mut out_a);
+    k_b.eval_range(&prg, 1, start, &mut out_b);
+    for j in 0..2021 {
+        assert_eq!(out_a[j], k_a.eval(&mut prg, 0, start + j as u32));
+        assert_eq!(out_a[j] ^ out_b[j], start + j as u32 == alpha);
+    }
+}
+
+#[test]
+fn generate_reproducible_keys_from_seed() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let seed: u128 = rng.gen();
+    let alpha: u32 = rng.gen();
+
+    let (k_a, k_b) =
+        DPFKeyAlpha1::<u32>::generate_keypair_from_rng(&mut prg, alpha, &mut seeded_rng(seed));
+    let (l_a, l_b) =
+        DPFKeyAlpha1::<u32>::generate_keypair_from_rng(&mut prg, alpha, &mut seeded_rng(seed));
+    assert_eq!((k_a.s, k_b.s), (l_a.s, l_b.s));
+    assert_eq!(k_a.cw, l_a.cw);
+    assert_eq!(k_a.cw_leaf, l_a.cw_leaf);
+
+    let out = k_a
+        .eval(&mut prg, 0, alpha)
+        .wrapping_add(k_b.eval(&mut prg, 1, alpha));
+    assert_eq!(out, 1u32);
+}
@@ -0,0 +1,99 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::fss::dpf::*;
+use sycret::fss::halftree::*;
+use sycret::stream::Prg;
+use sycret::utils::{ChaCha8Prg, Mmo};
+
+// Same checks for both constructions, through the DPFKey1 trait.
+fn check_alpha_and_not_alpha<K: DPFKey1<Input = u32, Output = u32>>(prg: &mut impl Prg) {
+    let mut rng = rand::thread_rng();
+    for _ in 0..16 {
+        let alpha: u32 = rng.gen();
+        let (k_a, k_b) = K::generate_keypair(prg, alpha);
+        let out = k_a
+            .eval(prg, 0, alpha)
+            .wrapping_add(k_b.eval(prg, 1, alpha));
+        assert_eq!(out, 1u32);
+
+        let not_alpha = alpha ^ (1 << rng.gen_range(0, 32));
+        let out = k_a
+            .eval(prg, 0, not_alpha)
+            .wrapping_add(k_b.eval(prg, 1, not_alpha));
+        assert_eq!(out, 0u32);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_alpha() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    check_alpha_and_not_alpha::<HalfTreeDPFKey>(&mut prg);
+    check_alpha_and_not_alpha::<DPFKeyAlpha1>(&mut prg);
+
+    // Any Prg gives the hash, with its first block.
+    let mut prg = ChaCha8Prg::new(&aes_keys);
+    check_alpha_and_not_alpha::<HalfTreeDPFKey>(&mut prg);
+}
+
+#[test]
+fn generate_and_evaluate_other_domains() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    for _ in 0..16 {
+        let alpha: u8 = rng.gen();
+        let (k_a, k_b) = HalfTreeDPFKey::<u8, u64>::generate_keypair(&mut prg, alpha);
+        assert_eq!(k_a.cw.len(), 8);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let out = k_a
+                .eval(&mut prg, 0, *x)
+                .wrapping_add(k_b.eval(&mut prg, 1, *x));
+            assert_eq!(out, (*x == alpha) as u64);
+        }
+
+        let alpha: u64 = rng.gen();
+        let (k_a, k_b) = HalfTreeDPFKey::<u64, bool>::generate_keypair(&mut prg, alpha);
+        assert_eq!(k_a.cw.len(), 64);
+        for x in [alpha, alpha ^ (1 << 63), alpha ^ 1].iter() {
+            let out = k_a.eval(&mut prg, 0, *x) ^ k_b.eval(&mut prg, 1, *x);
+            assert_eq!(out, *x == alpha);
+        }
+    }
+}
+
+#[test]
+fn evaluate_whole_domain() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    let alpha: u16 = rng.gen();
+    let (k_a, k_b) = HalfTreeDPFKey::<u16, u32>::generate_keypair(&mut prg, alpha);
+    let mut out_a = vec![0u32; 1 << 16];
+    let mut out_b = vec![0u32; 1 << 16];
+    k_a.eval_all(&prg, 0, &mut out_a);
+    k_b.eval_all(&prg, 1, &mut out_b);
+    for x in 0..(1 << 16) {
+        assert_eq!(
+            out_a[x].wrapping_add(out_b[x]),
+            (x == alpha as usize) as u32
+        );
+    }
+
+    // The range evaluation matches the point-wise evaluation, even when it is not aligned.
+    let alpha: u32 = rng.gen();
+    let (k_a, k_b) = HalfTreeDPFKey::<u32, u32>::generate_keypair(&mut prg, alpha);
+    let start = alpha.saturating_sub(1000).min(u32::MAX - 2021);
+    let mut out_a = vec![0u32; 2021];
+    let mut out_b = vec![0u32; 2021];
+    k_a.eval_range(&prg, 0, start, &mut out_a);
+    k_b.eval_range(&prg, 1, start, &mut out_b);
+    for j in 0..2021 {
+        let x = start + j as u32;
+        assert_eq!(out_a[j], k_a.eval(&mut prg, 0, x));
+        assert_eq!(out_a[j].wrapping_add(out_b[j]), (x == alpha) as u32);
+    }
+}
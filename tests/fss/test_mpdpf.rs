@@ -0,0 +1,102 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::fss::mpdpf::*;
+use sycret::utils::Mmo;
+
+#[test]
+fn generate_and_evaluate_multi_point() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let prg = Mmo::new(&aes_keys);
+
+    let log_domain = 14;
+    for &t in &[1usize, 2, 50] {
+        // Distinct random positions, with random payloads.
+        let mut points: Vec<(u32, u32)> = vec![];
+        while points.len() < t {
+            let alpha = rng.gen::<u32>() >> (32 - log_domain);
+            if points.iter().all(|(a, _)| *a != alpha) {
+                points.push((alpha, rng.gen()));
+            }
+        }
+        let (k_a, k_b) = MultiPointDPFKey::generate_keypair(&mut prg.clone(), log_domain, &points);
+
+        // The buckets are smaller than the domain.
+        if t == 50 {
+            assert!(k_a
+                .buckets
+                .iter()
+                .all(|bucket| bucket.cw.len() < log_domain as usize));
+        }
+
+        let mut out_a = vec![0u32; 1 << log_domain];
+        let mut out_b = vec![0u32; 1 << log_domain];
+        k_a.eval_all(&prg, 0, &mut out_a);
+        k_b.eval_all(&prg, 1, &mut out_b);
+        for x in 0..(1u32 << log_domain) {
+            let expected = points
+                .iter()
+                .find(|(alpha, _)| *alpha == x)
+                .map_or(0, |(_, beta)| *beta);
+            let i = x as usize;
+            assert_eq!(out_a[i].wrapping_add(out_b[i]), expected);
+        }
+    }
+}
+
+#[test]
+fn evaluate_multi_point_xor_output() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Every point of a small domain.
+    let points: Vec<(u8, bool)> = (0..=255u8).map(|x| (x, x % 3 == 0)).collect();
+    let (k_a, k_b) = MultiPointDPFKey::generate_keypair(&mut prg, 8, &points);
+    let mut out_a = vec![false; 256];
+    let mut out_b = vec![false; 256];
+    k_a.eval_all(&prg, 0, &mut out_a);
+    k_b.eval_all(&prg, 1, &mut out_b);
+    for x in 0..256 {
+        assert_eq!(out_a[x] ^ out_b[x], x % 3 == 0);
+    }
+}
+
+#[test]
+fn generate_multi_point_on_large_and_odd_domains() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let prg = Mmo::new(&aes_keys);
+
+    // Keygen only hashes the points, even on the whole 32 bits domain.
+    let points: Vec<(u32, u32)> = (0..64).map(|i| (i * 0x0400_0001, rng.gen())).collect();
+    let (k_a, _) = MultiPointDPFKey::generate_keypair(&mut prg.clone(), 32, &points);
+    assert!(k_a.buckets.iter().all(|bucket| bucket.cw.len() < 28));
+
+    // The hash functions walk back into odd domains.
+    let points: Vec<(u16, u64)> = vec![(0, 1), (300, rng.gen()), (511, rng.gen())];
+    let (k_a, k_b) = MultiPointDPFKey::generate_keypair(&mut prg.clone(), 9, &points);
+    let mut out_a = vec![0u64; 512];
+    let mut out_b = vec![0u64; 512];
+    k_a.eval_all(&prg, 0, &mut out_a);
+    k_b.eval_all(&prg, 1, &mut out_b);
+    for x in 0..512u16 {
+        let expected = points
+            .iter()
+            .find(|(alpha, _)| *alpha == x)
+            .map_or(0, |(_, beta)| *beta);
+        let i = x as usize;
+        assert_eq!(out_a[i].wrapping_add(out_b[i]), expected);
+    }
+}
+
+#[test]
+#[should_panic(expected = "The alphas must be distinct")]
+fn generate_multi_point_with_repeated_alphas() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let points: Vec<(u32, u32)> = vec![(3, 1), (5, 2), (3, 4)];
+    MultiPointDPFKey::generate_keypair(&mut prg, 8, &points);
+}
@@ -2,8 +2,11 @@ use rand::Rng;
 
 extern crate sycret;
 use sycret::eq::*;
-use sycret::stream::{FSSKey, Prg};
-use sycret::utils::Mmo;
+use sycret::stream::{eval_key_stream, prg_header, FSSKey, Prg, PrgId, RawKey, PRG_HEADER_LEN};
+use sycret::utils::{session_aes_keys, Mmo};
+use sycret::{
+    derive_aes_keys, eval, eval_with_aes_keys, keygen_with_aes_keys, keygen_with_params, n_aes_keys,
+};
 
 #[test]
 fn generate_and_evaluate_alpha() {
@@ -11,7 +14,7 @@ fn generate_and_evaluate_alpha() {
     for _ in 0..16 {
         let mut rng = rand::thread_rng();
         let aes_keys: [u128; 4] = rng.gen();
-        let mut prg = Mmo::from_slice(&aes_keys);
+        let mut prg = Mmo::new(&aes_keys);
         let (k_a, k_b) = EqKey::generate_keypair(&mut prg);
 
         // Recover alpha from the shares.
@@ -33,7 +36,7 @@ fn generate_and_evaluate_not_alpha() {
     for _ in 0..16 {
         let mut rng = rand::thread_rng();
         let aes_keys: [u128; 4] = rng.gen();
-        let mut prg = Mmo::from_slice(&aes_keys);
+        let mut prg = Mmo::new(&aes_keys);
         let (k_a, k_b) = EqKey::generate_keypair(&mut prg);
 
         // Recover alpha from the shares.
@@ -53,3 +56,344 @@ fn generate_and_evaluate_not_alpha() {
         assert_eq!(t_a_output.wrapping_add(t_b_output), 0u32);
     }
 }
+
+#[test]
+fn generate_and_evaluate_other_domains_through_raw_lines() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(EqKey::KEY_LEN, 621);
+    assert_eq!(GenericEqKey::<u16>::KEY_LEN, 2 + 16 + 18 * 16 + 4);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = GenericEqKey::<u16>::generate_keypair(&mut prg);
+
+        // Serialize and deserialize the keys.
+        let mut line_a = vec![0u8; GenericEqKey::<u16>::KEY_LEN];
+        let mut line_b = vec![0u8; GenericEqKey::<u16>::KEY_LEN];
+        let (k_a, k_b) = unsafe {
+            k_a.to_raw_line(line_a.as_mut_ptr());
+            k_b.to_raw_line(line_b.as_mut_ptr());
+            (
+                GenericEqKey::<u16>::from_raw_line(line_a.as_ptr()),
+                GenericEqKey::<u16>::from_raw_line(line_b.as_ptr()),
+            )
+        };
+
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output = k_a.eval(&mut prg, 0, *x);
+            let t_b_output = k_b.eval(&mut prg, 1, *x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x == alpha) as u32);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_64_bits_output() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(EqKey64::KEY_LEN, 604);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = EqKey64::generate_keypair(&mut prg);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output = k_a.eval(&mut prg, 0, *x);
+            let t_b_output = k_b.eval(&mut prg, 1, *x);
+
+            // The output bit is additively secret-shared in Z/2^64Z
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x == alpha) as u64);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_xor_output() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // The leaf correction word fits in a single byte.
+    assert_eq!(EqKeyXor::KEY_LEN, 597);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = EqKeyXor::generate_keypair(&mut prg);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for x in [alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output = k_a.eval(&mut prg, 0, *x);
+            let t_b_output = k_b.eval(&mut prg, 1, *x);
+
+            // The output bit is XOR secret-shared
+            assert_eq!(t_a_output ^ t_b_output, *x == alpha);
+        }
+    }
+}
+
+#[test]
+fn generate_reproducible_keys_from_seed() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let seed: u128 = rng.gen();
+
+    // The same seed gives the same raw lines, another seed does not.
+    let mut lines = vec![vec![0u8; EqKey::KEY_LEN]; 6];
+    let keys = [
+        EqKey::generate_keypair_from_seed(&mut prg, seed),
+        EqKey::generate_keypair_from_seed(&mut prg, seed),
+        EqKey::generate_keypair_from_seed(&mut prg, seed ^ 1),
+    ];
+    for (i, (k_a, k_b)) in keys.iter().enumerate() {
+        unsafe {
+            k_a.to_raw_line(lines[2 * i].as_mut_ptr());
+            k_b.to_raw_line(lines[2 * i + 1].as_mut_ptr());
+        }
+    }
+    assert_eq!(lines[0..2], lines[2..4]);
+    assert_ne!(lines[0], lines[4]);
+
+    // Seeded keys are still valid keys.
+    let (k_a, k_b) = &keys[0];
+    let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+    let out = k_a
+        .eval(&mut prg, 0, alpha)
+        .wrapping_add(k_b.eval(&mut prg, 1, alpha));
+    assert_eq!(out, 1u32);
+}
+
+#[test]
+fn generate_known_keys_from_seed() {
+    // Fixed public keys and seed: the key bytes must not change across versions.
+    let mut prg = Mmo::new(&[0, 1]);
+    let seed: u128 = 0x0123_4567_89ab_cdef_0011_2233_4455_6677;
+    let (k_a, k_b) = EqKey::generate_keypair_from_seed(&mut prg, seed);
+    let mut line_a = vec![0u8; EqKey::KEY_LEN];
+    let mut line_b = vec![0u8; EqKey::KEY_LEN];
+    unsafe {
+        k_a.to_raw_line(line_a.as_mut_ptr());
+        k_b.to_raw_line(line_b.as_mut_ptr());
+    }
+
+    // Share of alpha, seed, then the first correction word and its control bits.
+    let expected_a: [u8; 38] = [
+        147, 133, 156, 182, 112, 81, 37, 17, 203, 76, 65, 71, 125, 23, 8, 100, 171, 183, 239, 150,
+        190, 249, 230, 130, 225, 91, 233, 61, 100, 95, 192, 182, 71, 228, 76, 179, 1, 1,
+    ];
+    let expected_b: [u8; 38] = [
+        155, 203, 19, 56, 49, 64, 16, 59, 190, 176, 196, 240, 211, 196, 132, 151, 54, 140, 209,
+        254, 190, 249, 230, 130, 225, 91, 233, 61, 100, 95, 192, 182, 71, 228, 76, 179, 1, 1,
+    ];
+    assert_eq!(line_a[0..38], expected_a);
+    assert_eq!(line_b[0..38], expected_b);
+}
+
+#[test]
+fn generate_keys_for_given_alpha_through_ffi() {
+    let mut rng = rand::thread_rng();
+
+    // Rust API: the split of alpha is the one given by the dealer.
+    let aes_keys: [u128; 2] = [0, 1];
+    let mut prg = Mmo::new(&aes_keys);
+    let (alpha, mask): (u32, u32) = rng.gen();
+    let (k_a, k_b) = EqKey::generate_keypair_from_alpha(&mut prg, alpha, mask, &mut rng);
+    assert_eq!(k_b.alpha_share, mask);
+    assert_eq!(k_a.alpha_share, alpha.wrapping_sub(mask));
+    let out = k_a
+        .eval(&mut prg, 0, alpha)
+        .wrapping_add(k_b.eval(&mut prg, 1, alpha));
+    assert_eq!(out, 1u32);
+
+    // op_id = 18 implies equality keys with [alpha, mask] for each key.
+    // More keys than streams, so that the parameters are split between streams.
+    let op_id: usize = 18;
+    let n_values: usize = 300;
+    let params: Vec<u64> = (0..2 * n_values).map(|_| rng.gen::<u32>() as u64).collect();
+    let mut keys_a = vec![0u8; EqKey::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; EqKey::KEY_LEN * n_values];
+    unsafe {
+        keygen_with_params(
+            keys_a.as_mut_ptr(),
+            keys_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+            params.as_ptr(),
+            params.len(),
+        );
+    }
+
+    // Evaluate on alpha itself, and on alpha + 1 for odd lines.
+    let mut xs: Vec<u32> = vec![];
+    for i in 0..n_values {
+        let k_b = unsafe { EqKey::from_raw_line(keys_b.as_ptr().add(EqKey::KEY_LEN * i)) };
+        assert_eq!(k_b.alpha_share as u64, params[2 * i + 1]);
+        xs.push((params[2 * i] as u32).wrapping_add((i % 2) as u32));
+    }
+    let xs_bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+    let mut results_a = vec![0i64; n_values];
+    let mut results_b = vec![0i64; n_values];
+    unsafe {
+        eval(
+            0,
+            xs_bytes.as_ptr(),
+            keys_a.as_ptr(),
+            results_a.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+        eval(
+            1,
+            xs_bytes.as_ptr(),
+            keys_b.as_ptr(),
+            results_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+    }
+    for i in 0..n_values {
+        let out = (results_a[i] as u32).wrapping_add(results_b[i] as u32);
+        assert_eq!(out, (i % 2 == 0) as u32);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_with_session_aes_keys() {
+    let mut rng = rand::thread_rng();
+    let op_id: usize = 0;
+    let n_values: usize = 64;
+
+    // Public AES keys derived from the session id, the same for keygen and eval.
+    let session_id: u128 = rng.gen();
+    let n_keys = n_aes_keys(op_id);
+    let mut aes_keys = vec![0u8; 16 * n_keys];
+    unsafe {
+        derive_aes_keys(
+            session_id.to_le_bytes().as_ptr(),
+            aes_keys.as_mut_ptr(),
+            n_keys,
+        );
+    }
+    let expected: Vec<u8> = session_aes_keys(session_id, n_keys)
+        .iter()
+        .flat_map(|key| key.to_le_bytes().to_vec())
+        .collect();
+    assert_eq!(aes_keys, expected);
+
+    let line_len = PRG_HEADER_LEN + EqKey::KEY_LEN;
+    let mut keys_a = vec![0u8; line_len * n_values];
+    let mut keys_b = vec![0u8; line_len * n_values];
+    unsafe {
+        keygen_with_aes_keys(
+            keys_a.as_mut_ptr(),
+            keys_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+            std::ptr::null(),
+            0,
+            aes_keys.as_ptr(),
+            n_keys,
+        );
+    }
+
+    // The Rust API gives the same Prg from the same session,
+    // and each line records its keys after the Prg.
+    let mut prg = Mmo::<2>::from_vec(&session_aes_keys(session_id, n_keys));
+    let header = prg_header(PrgId::Mmo, &session_aes_keys(session_id, n_keys));
+    assert_ne!(header, prg_header(PrgId::Mmo, &[0, 1]));
+    let mut xs: Vec<u32> = vec![];
+    for i in 0..n_values {
+        assert_eq!(keys_a[line_len * i..line_len * i + PRG_HEADER_LEN], header);
+        let (k_a, k_b) = unsafe {
+            (
+                EqKey::from_raw_line(keys_a.as_ptr().add(line_len * i + PRG_HEADER_LEN)),
+                EqKey::from_raw_line(keys_b.as_ptr().add(line_len * i + PRG_HEADER_LEN)),
+            )
+        };
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        let out = k_a
+            .eval(&mut prg, 0, alpha)
+            .wrapping_add(k_b.eval(&mut prg, 1, alpha));
+        assert_eq!(out, 1u32);
+        xs.push(alpha);
+    }
+    let xs_bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+
+    // Eval with the session keys recovers the point.
+    let mut results = vec![0i64; 2 * n_values];
+    for (party_id, keys) in [&keys_a, &keys_b].iter().enumerate() {
+        unsafe {
+            eval_with_aes_keys(
+                party_id,
+                xs_bytes.as_ptr(),
+                keys.as_ptr(),
+                results.as_mut_ptr().add(party_id * n_values),
+                n_values,
+                2,
+                op_id,
+                aes_keys.as_ptr(),
+                n_keys,
+            );
+        }
+    }
+    for i in 0..n_values {
+        assert_eq!(
+            (results[i] as u32).wrapping_add(results[n_values + i] as u32),
+            1
+        );
+    }
+}
+
+#[test]
+#[should_panic(expected = "Key generated with other public keys")]
+fn evaluate_with_other_aes_keys() {
+    let op_id: usize = 0;
+    let n_values: usize = 4;
+    let n_keys = n_aes_keys(op_id);
+    let session_keys = |session_id: u128| -> Vec<u8> {
+        session_aes_keys(session_id, n_keys)
+            .iter()
+            .flat_map(|key| key.to_le_bytes().to_vec())
+            .collect()
+    };
+
+    let line_len = PRG_HEADER_LEN + EqKey::KEY_LEN;
+    let mut keys_a = vec![0u8; line_len * n_values];
+    let mut keys_b = vec![0u8; line_len * n_values];
+    unsafe {
+        keygen_with_aes_keys(
+            keys_a.as_mut_ptr(),
+            keys_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+            std::ptr::null(),
+            0,
+            session_keys(1).as_ptr(),
+            n_keys,
+        );
+    }
+
+    // Eval is given the keys of another session, through the stream since a panic
+    // cannot unwind out of the C ABI.
+    let xs = vec![0u8; 4 * n_values];
+    let mut results = vec![0i64; n_values];
+    eval_key_stream(
+        0,
+        &session_aes_keys(2, n_keys),
+        0,
+        n_values,
+        xs.as_ptr() as usize,
+        keys_a.as_ptr() as usize,
+        results.as_mut_ptr() as usize,
+        op_id,
+        PrgId::Mmo,
+        true,
+    );
+}
@@ -0,0 +1,156 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::interval::*;
+use sycret::stream::RawKey;
+use sycret::utils::Mmo;
+use sycret::{eval, keygen_with_params};
+
+#[test]
+fn generate_and_evaluate_all_inputs_on_8_bits() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Include the edge cases of the domain.
+    let bounds: [(u8, u8); 5] = [(0, 255), (0, 0), (255, 255), (17, 200), (100, 101)];
+    for &(lower, upper) in bounds.iter() {
+        let (k_a, k_b) = GenericIntervalKey::<u8>::generate_keypair(&mut prg, lower, upper);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+
+        for x in 0..=255u8 {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output: u32 = k_a.eval(&mut prg, 0, masked_x);
+            let b_output: u32 = k_b.eval(&mut prg, 1, masked_x);
+            let expected = (lower <= x && x <= upper) as u32;
+            assert_eq!(a_output.wrapping_add(b_output), expected);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_through_raw_lines() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(IntervalKey::KEY_LEN, 932);
+
+    for _ in 0..16 {
+        let lower: u32 = rng.gen();
+        let upper = lower.saturating_add(rng.gen::<u32>() % 1000);
+        let (k_a, k_b) = IntervalKey::generate_keypair(&mut prg, lower, upper);
+
+        // Serialize and deserialize the keys.
+        let mut line_a = vec![0u8; IntervalKey::KEY_LEN];
+        let mut line_b = vec![0u8; IntervalKey::KEY_LEN];
+        let (k_a, k_b) = unsafe {
+            k_a.to_raw_line(line_a.as_mut_ptr());
+            k_b.to_raw_line(line_b.as_mut_ptr());
+            (
+                IntervalKey::from_raw_line(line_a.as_ptr()),
+                IntervalKey::from_raw_line(line_b.as_ptr()),
+            )
+        };
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+
+        for &x in [lower.wrapping_sub(1), lower, upper, upper.wrapping_add(1)].iter() {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output = k_a.eval(&mut prg, 0, masked_x);
+            let b_output = k_b.eval(&mut prg, 1, masked_x);
+            let expected = (lower <= x && x <= upper) as u32;
+            assert_eq!(a_output.wrapping_add(b_output), expected);
+        }
+    }
+}
+
+#[test]
+fn batch_generate_and_evaluate() {
+    // op_id = 6 implies interval containment keys, with bounds as parameters
+    let op_id: usize = 6;
+    let n_values: usize = 16;
+    let params: [u64; 2] = [1000, 2000];
+
+    let mut keys_a = vec![0u8; IntervalKey::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; IntervalKey::KEY_LEN * n_values];
+    unsafe {
+        keygen_with_params(
+            keys_a.as_mut_ptr(),
+            keys_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+            params.as_ptr(),
+            params.len(),
+        );
+    }
+
+    // Evaluate around both bounds
+    let offsets: [u32; 4] = [999, 1000, 2000, 2001];
+    let mut xs: Vec<u32> = vec![];
+    for i in 0..n_values {
+        let (k_a, k_b) = unsafe {
+            (
+                IntervalKey::from_raw_line(keys_a.as_ptr().add(IntervalKey::KEY_LEN * i)),
+                IntervalKey::from_raw_line(keys_b.as_ptr().add(IntervalKey::KEY_LEN * i)),
+            )
+        };
+        assert_eq!((k_a.lower, k_a.upper), (1000, 2000));
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        xs.push(alpha.wrapping_add(offsets[i % 4]));
+    }
+    let xs_bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+
+    let mut results_a = vec![0i64; n_values];
+    let mut results_b = vec![0i64; n_values];
+    unsafe {
+        eval(
+            0,
+            xs_bytes.as_ptr(),
+            keys_a.as_ptr(),
+            results_a.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+        eval(
+            1,
+            xs_bytes.as_ptr(),
+            keys_b.as_ptr(),
+            results_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+    }
+
+    for i in 0..n_values {
+        let expected = (i % 4 == 1 || i % 4 == 2) as u32;
+        let result = (results_a[i] as u32).wrapping_add(results_b[i] as u32);
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_multiple_intervals_on_8_bits() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Contiguous pieces of a spline, plus overlapping and degenerate intervals.
+    let intervals: [(u8, u8); 6] = [(0, 63), (64, 127), (128, 255), (0, 255), (10, 10), (60, 70)];
+    for _ in 0..4 {
+        let (k_a, k_b) = GenericMicKey::<u8, u64>::generate_keypair(&mut prg, &intervals);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+
+        for x in 0..=255u8 {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output = k_a.eval(&mut prg, 0, masked_x);
+            let b_output = k_b.eval(&mut prg, 1, masked_x);
+            for (i, &(lower, upper)) in intervals.iter().enumerate() {
+                let expected = (lower <= x && x <= upper) as u64;
+                assert_eq!(a_output[i].wrapping_add(b_output[i]), expected);
+            }
+        }
+    }
+}
@@ -2,13 +2,14 @@ use rand::Rng;
 
 extern crate sycret;
 use sycret::le::*;
-use sycret::stream::{FSSKey, Prg};
+use sycret::stream::{generate_key_stream, FSSKey, PrgId, RawKey};
 use sycret::utils::Mmo;
+use sycret::{eval, keygen, keygen_with_params};
 
 fn eval_on_alpha_with_offset(offset: i32) -> (u32, u32) {
     let mut rng = rand::thread_rng();
     let aes_keys: [u128; 4] = rng.gen();
-    let mut prg = Mmo::from_slice(&aes_keys);
+    let mut prg = Mmo::new(&aes_keys);
 
     let (k_a, k_b) = LeKey::generate_keypair(&mut prg);
 
@@ -23,12 +24,12 @@ fn eval_on_alpha_with_offset(offset: i32) -> (u32, u32) {
     }
 
     // Clean ciphers
-    let mut prg = Mmo::from_slice(&aes_keys);
+    let mut prg = Mmo::new(&aes_keys);
 
     // Evaluate separately on alpha
     let a_output = k_a.eval(&mut prg, 0, alpha);
 
-    let mut prg = Mmo::from_slice(&aes_keys);
+    let mut prg = Mmo::new(&aes_keys);
     let b_output = k_b.eval(&mut prg, 1, alpha);
 
     (a_output, b_output)
@@ -58,3 +59,308 @@ fn generate_and_evaluate_strictly_greater_than_alpha() {
         assert_eq!(a_output.wrapping_add(b_output), 0u32);
     }
 }
+
+#[test]
+fn generate_and_evaluate_other_domains_through_raw_lines() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(LeKey::KEY_LEN, 920);
+    assert_eq!(GenericLeKey::<u64>::KEY_LEN, 8 + 16 + 24 * 64 + 4 * 65);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = GenericLeKey::<u64>::generate_keypair(&mut prg);
+
+        // Serialize and deserialize the keys.
+        let mut line_a = vec![0u8; GenericLeKey::<u64>::KEY_LEN];
+        let mut line_b = vec![0u8; GenericLeKey::<u64>::KEY_LEN];
+        let (k_a, k_b) = unsafe {
+            k_a.to_raw_line(line_a.as_mut_ptr());
+            k_b.to_raw_line(line_b.as_mut_ptr());
+            (
+                GenericLeKey::<u64>::from_raw_line(line_a.as_ptr()),
+                GenericLeKey::<u64>::from_raw_line(line_b.as_ptr()),
+            )
+        };
+
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for x in [alpha.wrapping_sub(1), alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output = k_a.eval(&mut prg, 0, *x);
+            let t_b_output = k_b.eval(&mut prg, 1, *x);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (*x <= alpha) as u32);
+        }
+    }
+}
+
+#[test]
+fn batch_generate_and_evaluate_64_bits_output() {
+    // op_id = 3 implies Le keys with shares in Z/2^64Z
+    let op_id: usize = 3;
+    let n_values: usize = 16;
+    assert_eq!(LeKey64::KEY_LEN, 1180);
+
+    let mut keys_a = vec![0u8; LeKey64::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; LeKey64::KEY_LEN * n_values];
+    unsafe {
+        keygen(keys_a.as_mut_ptr(), keys_b.as_mut_ptr(), n_values, 2, op_id);
+    }
+
+    // Evaluate on alpha - 1, alpha and alpha + 1
+    let mut xs: Vec<u32> = vec![];
+    for i in 0..n_values {
+        let (k_a, k_b) = unsafe {
+            (
+                LeKey64::from_raw_line(keys_a.as_ptr().add(LeKey64::KEY_LEN * i)),
+                LeKey64::from_raw_line(keys_b.as_ptr().add(LeKey64::KEY_LEN * i)),
+            )
+        };
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        xs.push(alpha.wrapping_add(i as u32 % 3).wrapping_sub(1));
+    }
+    let xs_bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+
+    let mut results_a = vec![0i64; n_values];
+    let mut results_b = vec![0i64; n_values];
+    unsafe {
+        eval(
+            0,
+            xs_bytes.as_ptr(),
+            keys_a.as_ptr(),
+            results_a.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+        eval(
+            1,
+            xs_bytes.as_ptr(),
+            keys_b.as_ptr(),
+            results_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+    }
+
+    for i in 0..n_values {
+        let expected = (i % 3 != 2) as i64;
+        assert_eq!(results_a[i].wrapping_add(results_b[i]), expected);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_xor_output_through_raw_lines() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(LeKeyXor::KEY_LEN, 725);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = LeKeyXor::generate_keypair(&mut prg);
+
+        let mut line_a = vec![0u8; LeKeyXor::KEY_LEN];
+        let mut line_b = vec![0u8; LeKeyXor::KEY_LEN];
+        let (k_a, k_b) = unsafe {
+            k_a.to_raw_line(line_a.as_mut_ptr());
+            k_b.to_raw_line(line_b.as_mut_ptr());
+            (
+                LeKeyXor::from_raw_line(line_a.as_ptr()),
+                LeKeyXor::from_raw_line(line_b.as_ptr()),
+            )
+        };
+
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for x in [alpha.wrapping_sub(1), alpha, alpha.wrapping_add(1)].iter() {
+            let t_a_output = k_a.eval(&mut prg, 0, *x);
+            let t_b_output = k_b.eval(&mut prg, 1, *x);
+
+            // The output bit is XOR secret-shared
+            assert_eq!(t_a_output ^ t_b_output, *x <= alpha);
+        }
+    }
+}
+
+#[test]
+fn evaluate_range() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    let (k_a, k_b) = GenericLeKey::<u16>::generate_keypair(&mut prg);
+    let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+    let start = alpha.saturating_sub(100).min(u16::MAX - 200);
+    let mut out_a = vec![0u32; 200];
+    let mut out_b = vec![0u32; 200];
+    k_a.eval_range(&prg, 0, start, &mut out_a);
+    k_b.eval_range(&prg, 1, start, &mut out_b);
+    for j in 0..200 {
+        let x = start + j as u16;
+        assert_eq!(out_a[j].wrapping_add(out_b[j]), (x <= alpha) as u32);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_signed_order() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Exhaustive on 8 bits, where -128 <= ... <= -1 < 0 <= ... <= 127.
+    for _ in 0..8 {
+        let (k_a, k_b) = GenericSignedLeKey::<u8>::generate_keypair(&mut prg);
+        let alpha = k_a.0.alpha_share.wrapping_add(k_b.0.alpha_share) as i8;
+        for x in -128..=127i8 {
+            let t_a_output: u32 = k_a.eval(&mut prg, 0, x as u8);
+            let t_b_output: u32 = k_b.eval(&mut prg, 1, x as u8);
+            assert_eq!(t_a_output.wrapping_add(t_b_output), (x <= alpha) as u32);
+        }
+    }
+
+    // Same layout as LeKey on 32 bits.
+    assert_eq!(SignedLeKey::KEY_LEN, 920);
+    let (k_a, k_b) = SignedLeKey::generate_keypair(&mut prg);
+    let alpha = k_a.0.alpha_share.wrapping_add(k_b.0.alpha_share) as i32;
+    for &x in [
+        i32::MIN,
+        -1,
+        0,
+        1,
+        i32::MAX,
+        alpha.wrapping_sub(1),
+        alpha,
+        alpha.wrapping_add(1),
+    ]
+    .iter()
+    {
+        let t_a_output = k_a.eval(&mut prg, 0, x as u32);
+        let t_b_output = k_b.eval(&mut prg, 1, x as u32);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), (x <= alpha) as u32);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_other_directions() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(LtKey::KEY_LEN, 920);
+
+    for _ in 0..16 {
+        let (lt_a, lt_b) = LtKey::generate_keypair(&mut prg);
+        let (gt_a, gt_b) = GtKey::generate_keypair(&mut prg);
+        let (ge_a, ge_b) = GeKey::generate_keypair(&mut prg);
+        let lt_alpha = lt_a.alpha_share.wrapping_add(lt_b.alpha_share);
+        let gt_alpha = gt_a.alpha_share.wrapping_add(gt_b.alpha_share);
+        let ge_alpha = ge_a.alpha_share.wrapping_add(ge_b.alpha_share);
+        for offset in [u32::MAX, 0, 1].iter() {
+            let x = lt_alpha.wrapping_add(*offset);
+            let lt = lt_a
+                .eval(&mut prg, 0, x)
+                .wrapping_add(lt_b.eval(&mut prg, 1, x));
+            assert_eq!(lt, (x < lt_alpha) as u32);
+
+            let x = gt_alpha.wrapping_add(*offset);
+            let gt = gt_a
+                .eval(&mut prg, 0, x)
+                .wrapping_add(gt_b.eval(&mut prg, 1, x));
+            assert_eq!(gt, (x > gt_alpha) as u32);
+
+            let x = ge_alpha.wrapping_add(*offset);
+            let ge = ge_a
+                .eval(&mut prg, 0, x)
+                .wrapping_add(ge_b.eval(&mut prg, 1, x));
+            assert_eq!(ge, (x >= ge_alpha) as u32);
+        }
+    }
+}
+
+#[test]
+fn generate_reproducible_keys_from_rng() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let seed: [u8; 32] = rng.gen();
+
+    let (k_a, k_b) = LeKey::generate_keypair_from_rng(&mut prg, &mut StdRng::from_seed(seed));
+    let (l_a, l_b) = LeKey::generate_keypair_from_rng(&mut prg, &mut StdRng::from_seed(seed));
+    assert_eq!(k_a.alpha_share, l_a.alpha_share);
+    assert_eq!((k_a.s, k_b.s), (l_a.s, l_b.s));
+    assert_eq!(k_b.cw, l_b.cw);
+    assert_eq!(k_b.cw_leaf, l_b.cw_leaf);
+
+    let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+    let out = k_a
+        .eval(&mut prg, 0, alpha)
+        .wrapping_add(k_b.eval(&mut prg, 1, alpha));
+    assert_eq!(out, 1u32);
+}
+
+#[test]
+fn generate_keys_for_given_alpha_through_ffi() {
+    let mut rng = rand::thread_rng();
+
+    // op_id = 19 implies comparison keys with [alpha, mask] for each key.
+    let op_id: usize = 19;
+    let n_values: usize = 200;
+    let params: Vec<u64> = (0..2 * n_values).map(|_| rng.gen::<u32>() as u64).collect();
+    let mut keys_a = vec![0u8; LeKey::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; LeKey::KEY_LEN * n_values];
+    unsafe {
+        keygen_with_params(
+            keys_a.as_mut_ptr(),
+            keys_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+            params.as_ptr(),
+            params.len(),
+        );
+    }
+
+    // The keys match the given split, and compare with the given alpha.
+    let aes_keys: [u128; 3] = [0, 1, 2];
+    let mut prg = Mmo::new(&aes_keys);
+    for i in 0..n_values {
+        let (k_a, k_b) = unsafe {
+            (
+                LeKey::from_raw_line(keys_a.as_ptr().add(LeKey::KEY_LEN * i)),
+                LeKey::from_raw_line(keys_b.as_ptr().add(LeKey::KEY_LEN * i)),
+            )
+        };
+        let (alpha, mask) = (params[2 * i] as u32, params[2 * i + 1] as u32);
+        assert_eq!(k_b.alpha_share, mask);
+        assert_eq!(k_a.alpha_share, alpha.wrapping_sub(mask));
+        let x = alpha.wrapping_add((i % 2) as u32);
+        let out = k_a
+            .eval(&mut prg, 0, x)
+            .wrapping_add(k_b.eval(&mut prg, 1, x));
+        assert_eq!(out, (x <= alpha) as u32);
+    }
+}
+
+#[test]
+#[should_panic(expected = "op_id 19 expects [alpha, mask] pairs")]
+fn generate_keys_for_given_alpha_without_mask() {
+    // The last key has no mask. Through the stream, since a panic cannot unwind
+    // out of the C ABI.
+    let n_values: usize = 2;
+    let mut keys_a = vec![0u8; LeKey::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; LeKey::KEY_LEN * n_values];
+    generate_key_stream(
+        &[0, 1, 2],
+        0,
+        n_values,
+        keys_a.as_mut_ptr() as usize,
+        keys_b.as_mut_ptr() as usize,
+        19,
+        &[5, 3, 7],
+        PrgId::Mmo,
+        false,
+    );
+}
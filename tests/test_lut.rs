@@ -0,0 +1,87 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::lut::*;
+use sycret::utils::Mmo;
+
+#[test]
+fn generate_and_evaluate_8_bits_table() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let prg = Mmo::new(&aes_keys);
+
+    // Some nonlinear function on quantized inputs.
+    let table: Vec<u32> = (0..256u32).map(|x| x * x / 7 + 3).collect();
+    for _ in 0..4 {
+        let (k_a, k_b) = LutKey::generate_keypair(&mut prg.clone(), 8);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for x in 0..=255u8 {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output = k_a.eval(&prg, 0, masked_x, &table);
+            let b_output = k_b.eval(&prg, 1, masked_x, &table);
+            assert_eq!(a_output.wrapping_add(b_output), table[x as usize]);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_other_table_sizes() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let prg = Mmo::new(&aes_keys);
+
+    // Largest table, with 64-bit entries.
+    let table: Vec<u64> = (0..(1 << 16)).map(|_| rng.gen()).collect();
+    let (k_a, k_b) = GenericLutKey::<u16, u64>::generate_keypair(&mut prg.clone(), 16);
+    let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+    for _ in 0..4 {
+        let x: u16 = rng.gen();
+        let masked_x = x.wrapping_add(alpha);
+        let a_output = k_a.eval(&prg, 0, masked_x, &table);
+        let b_output = k_b.eval(&prg, 1, masked_x, &table);
+        assert_eq!(a_output.wrapping_add(b_output), table[x as usize]);
+    }
+
+    // Table of 2^10 bits, with XOR shares.
+    let table: Vec<bool> = (0..(1 << 10)).map(|_| rng.gen()).collect();
+    let (k_a, k_b) = GenericLutKey::<u16, bool>::generate_keypair(&mut prg.clone(), 10);
+    let alpha = (k_a.alpha_share + k_b.alpha_share) % (1 << 10);
+    for _ in 0..64 {
+        let x = rng.gen::<u16>() % (1 << 10);
+        let masked_x = (x + alpha) % (1 << 10);
+        let a_output = k_a.eval(&prg, 0, masked_x, &table);
+        let b_output = k_b.eval(&prg, 1, masked_x, &table);
+        assert_eq!(a_output ^ b_output, table[x as usize]);
+    }
+}
+
+#[test]
+fn batch_evaluate_with_one_buffer() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 2] = rng.gen();
+    let prg = Mmo::new(&aes_keys);
+
+    let table: Vec<u32> = (0..(1 << 6)).map(|_| rng.gen()).collect();
+    let (keys_a, keys_b): (Vec<_>, Vec<_>) = (0..16)
+        .map(|_| LutKey::generate_keypair(&mut prg.clone(), 6))
+        .unzip();
+    // The tree only has one level per bit of the table index.
+    assert_eq!(keys_a[0].cw.len(), 6);
+
+    let plain_xs: Vec<u8> = (0..16).map(|_| rng.gen::<u8>() % (1 << 6)).collect();
+    let xs: Vec<u8> = plain_xs
+        .iter()
+        .zip(keys_a.iter().zip(keys_b.iter()))
+        .map(|(x, (k_a, k_b))| (x + k_a.alpha_share + k_b.alpha_share) % (1 << 6))
+        .collect();
+    let mut results_a = vec![0u32; 16];
+    let mut results_b = vec![0u32; 16];
+    LutKey::eval_batch(&keys_a, &prg, 0, &xs, &table, &mut results_a);
+    LutKey::eval_batch(&keys_b, &prg, 1, &xs, &table, &mut results_b);
+    for i in 0..16 {
+        assert_eq!(
+            results_a[i].wrapping_add(results_b[i]),
+            table[plain_xs[i] as usize]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use rand::Rng;
+use std::cell::RefCell;
+use std::slice;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+extern crate sycret;
+use sycret::maxpool::*;
+use sycret::utils::{seeded_rng, Mmo};
+use sycret::{maxpool_eval, maxpool_keygen, maxpool_material_len};
+
+struct LocalChannel {
+    tx: Sender<Vec<u32>>,
+    rx: Receiver<Vec<u32>>,
+}
+
+impl Channel for LocalChannel {
+    fn exchange(&mut self, shares: &[u32]) -> Vec<u32> {
+        self.tx.send(shares.to_vec()).unwrap();
+        self.rx.recv().unwrap()
+    }
+}
+
+fn local_channels() -> (LocalChannel, LocalChannel) {
+    let (tx_a, rx_b) = channel();
+    let (tx_b, rx_a) = channel();
+    (
+        LocalChannel { tx: tx_a, rx: rx_a },
+        LocalChannel { tx: tx_b, rx: rx_b },
+    )
+}
+
+// The channel of the party running on this thread, for the C callback.
+thread_local! {
+    static THREAD_CHANNEL: RefCell<Option<LocalChannel>> = const { RefCell::new(None) };
+}
+
+unsafe extern "C" fn thread_exchange(shares: *const u32, other_shares: *mut u32, n_shares: usize) {
+    THREAD_CHANNEL.with(|channel| {
+        let other = channel
+            .borrow_mut()
+            .as_mut()
+            .unwrap()
+            .exchange(slice::from_raw_parts(shares, n_shares));
+        slice::from_raw_parts_mut(other_shares, n_shares).copy_from_slice(&other);
+    });
+}
+
+#[test]
+fn maxpool_and_argmax_on_batches_of_windows() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    for &k in &[1usize, 2, 3, 4, 9] {
+        let n_windows = 5;
+
+        // Small signed values, with distinct entries so that the argmax is unique.
+        let mut xs = vec![];
+        for _ in 0..n_windows {
+            let mut window: Vec<i32> = vec![];
+            while window.len() < k {
+                let x = rng.gen_range(-1000, 1000);
+                if !window.contains(&x) {
+                    window.push(x);
+                }
+            }
+            xs.extend(window);
+        }
+        let xs_a: Vec<u32> = (0..xs.len()).map(|_| rng.gen()).collect();
+        let xs_b: Vec<u32> = xs
+            .iter()
+            .zip(xs_a.iter())
+            .map(|(&x, &a)| (x as u32).wrapping_sub(a))
+            .collect();
+
+        let (materials_a, materials_b): (Vec<_>, Vec<_>) = (0..n_windows)
+            .map(|_| MaxpoolMaterial::generate_pair(&mut prg, k))
+            .unzip();
+
+        let (mut channel_a, mut channel_b) = local_channels();
+        let mut prg_b = prg.clone();
+        let bob =
+            thread::spawn(move || maxpool(&mut prg_b, 1, &mut channel_b, &xs_b, &materials_b));
+        let (max_a, argmax_a) = maxpool(&mut prg, 0, &mut channel_a, &xs_a, &materials_a);
+        let (max_b, argmax_b) = bob.join().unwrap();
+
+        for (w, window) in xs.chunks(k).enumerate() {
+            let (i_max, &max) = window.iter().enumerate().max_by_key(|(_, &x)| x).unwrap();
+            assert_eq!(max_a[w].wrapping_add(max_b[w]) as i32, max);
+            for i in 0..k {
+                let bit = argmax_a[w * k + i].wrapping_add(argmax_b[w * k + i]);
+                assert_eq!(bit, (i == i_max) as u32);
+            }
+        }
+    }
+}
+
+#[test]
+fn generate_reproducible_material_from_seed() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let seed: u128 = rng.gen();
+    let k = 3;
+
+    // The same seed gives the same raw material, another seed does not.
+    let mut lines = vec![vec![0u8; MaxpoolMaterial::raw_len(k)]; 6];
+    let materials = [
+        MaxpoolMaterial::generate_pair_from_rng(&mut prg, k, &mut seeded_rng(seed)),
+        MaxpoolMaterial::generate_pair_from_rng(&mut prg, k, &mut seeded_rng(seed)),
+        MaxpoolMaterial::generate_pair_from_rng(&mut prg, k, &mut seeded_rng(seed ^ 1)),
+    ];
+    for (i, (material_a, material_b)) in materials.iter().enumerate() {
+        unsafe {
+            material_a.to_raw_line(lines[2 * i].as_mut_ptr());
+            material_b.to_raw_line(lines[2 * i + 1].as_mut_ptr());
+        }
+    }
+    assert_eq!(lines[0..2], lines[2..4]);
+    assert_ne!(lines[0], lines[4]);
+}
+
+#[test]
+fn maxpool_through_the_c_abi() {
+    let mut rng = rand::thread_rng();
+    let k: usize = 4;
+    let n_windows: usize = 6;
+
+    let line_len = maxpool_material_len(k);
+    let mut materials_a = vec![0u8; line_len * n_windows];
+    let mut materials_b = vec![0u8; line_len * n_windows];
+    unsafe {
+        maxpool_keygen(
+            materials_a.as_mut_ptr(),
+            materials_b.as_mut_ptr(),
+            n_windows,
+            k,
+            std::ptr::null(),
+            0,
+        );
+    }
+
+    let xs: Vec<i32> = (0..n_windows * k)
+        .map(|i| rng.gen_range(-1000, 1000) * (k * n_windows) as i32 + i as i32)
+        .collect();
+    let xs_a: Vec<u32> = (0..xs.len()).map(|_| rng.gen()).collect();
+    let xs_b: Vec<u32> = xs
+        .iter()
+        .zip(xs_a.iter())
+        .map(|(&x, &a)| (x as u32).wrapping_sub(a))
+        .collect();
+
+    // Each party runs on its own thread, with its channel for the callback.
+    let (channel_a, channel_b) = local_channels();
+    let party = |party_id: usize, channel: LocalChannel, xs: Vec<u32>, materials: Vec<u8>| {
+        thread::spawn(move || {
+            THREAD_CHANNEL.with(|c| *c.borrow_mut() = Some(channel));
+            let mut max = vec![0u32; n_windows];
+            let mut argmax = vec![0u32; n_windows * k];
+            unsafe {
+                maxpool_eval(
+                    party_id,
+                    xs.as_ptr(),
+                    materials.as_ptr(),
+                    max.as_mut_ptr(),
+                    argmax.as_mut_ptr(),
+                    n_windows,
+                    k,
+                    std::ptr::null(),
+                    0,
+                    thread_exchange,
+                );
+            }
+            (max, argmax)
+        })
+    };
+    let alice = party(0, channel_a, xs_a, materials_a);
+    let bob = party(1, channel_b, xs_b, materials_b);
+    let (max_a, argmax_a) = alice.join().unwrap();
+    let (max_b, argmax_b) = bob.join().unwrap();
+
+    for (w, window) in xs.chunks(k).enumerate() {
+        let (i_max, &max) = window.iter().enumerate().max_by_key(|(_, &x)| x).unwrap();
+        assert_eq!(max_a[w].wrapping_add(max_b[w]) as i32, max);
+        for i in 0..k {
+            let bit = argmax_a[w * k + i].wrapping_add(argmax_b[w * k + i]);
+            assert_eq!(bit, (i == i_max) as u32);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::msb::*;
+use sycret::stream::{FSSKey, RawKey};
+use sycret::utils::Mmo;
+use sycret::{eval, keygen};
+
+#[test]
+fn generate_and_evaluate_msb_on_all_inputs() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    for _ in 0..8 {
+        let (k_a, k_b) = GenericMsbKey::<u8>::generate_keypair(&mut prg);
+        let (x_a, x_b) = GenericMsbKey::<u8, bool>::generate_keypair(&mut prg);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        let alpha_xor = x_a.alpha_share.wrapping_add(x_b.alpha_share);
+        for x in 0..=255u8 {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output: u32 = k_a.eval(&mut prg, 0, masked_x);
+            let b_output: u32 = k_b.eval(&mut prg, 1, masked_x);
+            assert_eq!(a_output.wrapping_add(b_output), (x >> 7) as u32);
+
+            let masked_x = x.wrapping_add(alpha_xor);
+            let a_output = x_a.eval(&mut prg, 0, masked_x);
+            let b_output = x_b.eval(&mut prg, 1, masked_x);
+            assert_eq!(a_output ^ b_output, x >= 128);
+        }
+    }
+}
+
+#[test]
+fn generate_and_evaluate_bits_through_raw_lines() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(MsbKey::KEY_LEN, 896);
+    assert_eq!(MsbKeyXor::KEY_LEN, 704);
+    assert_eq!(BitDecompositionKey::KEY_LEN, 14660);
+    assert_eq!(BitDecompositionKeyXor::KEY_LEN, 11492);
+
+    let (k_a, k_b) = BitDecompositionKeyXor::generate_keypair(&mut prg);
+    // The key of bit i only has a tree on the i lower bits.
+    for (i, key) in k_a.bits.iter().enumerate() {
+        assert_eq!(key.cw.len(), i);
+    }
+
+    // Serialize and deserialize the keys.
+    let mut line_a = vec![0u8; BitDecompositionKeyXor::KEY_LEN];
+    let mut line_b = vec![0u8; BitDecompositionKeyXor::KEY_LEN];
+    let (k_a, k_b) = unsafe {
+        k_a.to_raw_line(line_a.as_mut_ptr());
+        k_b.to_raw_line(line_b.as_mut_ptr());
+        (
+            BitDecompositionKeyXor::from_raw_line(line_a.as_ptr()),
+            BitDecompositionKeyXor::from_raw_line(line_b.as_ptr()),
+        )
+    };
+    let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+
+    let mut xs: Vec<u32> = vec![0, 1, u32::MAX, 0u32.wrapping_sub(alpha)];
+    xs.extend((0..16).map(|_| rng.gen::<u32>()));
+    for &x in xs.iter() {
+        let masked_x = x.wrapping_add(alpha);
+        let a_output = k_a.eval(&mut prg, 0, masked_x);
+        let b_output = k_b.eval(&mut prg, 1, masked_x);
+        for i in 0..32 {
+            assert_eq!(a_output[i] ^ b_output[i], (x >> i) & 1 == 1);
+        }
+    }
+}
+
+#[test]
+fn batch_generate_and_evaluate_bits() {
+    // op_id = 16 implies bit decomposition keys with shares in Z/2^32Z
+    let op_id: usize = 16;
+    let n_values: usize = 4;
+
+    let mut keys_a = vec![0u8; BitDecompositionKey::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; BitDecompositionKey::KEY_LEN * n_values];
+    unsafe {
+        keygen(keys_a.as_mut_ptr(), keys_b.as_mut_ptr(), n_values, 2, op_id);
+    }
+
+    let mut rng = rand::thread_rng();
+    let plain_xs: Vec<u32> = (0..n_values).map(|_| rng.gen::<u32>()).collect();
+    let mut xs: Vec<u32> = vec![];
+    for (i, plain_x) in plain_xs.iter().enumerate() {
+        let (alpha_a, alpha_b) = unsafe {
+            (
+                BitDecompositionKey::from_raw_line(
+                    keys_a.as_ptr().add(BitDecompositionKey::KEY_LEN * i),
+                )
+                .alpha_share,
+                BitDecompositionKey::from_raw_line(
+                    keys_b.as_ptr().add(BitDecompositionKey::KEY_LEN * i),
+                )
+                .alpha_share,
+            )
+        };
+        xs.push(plain_x.wrapping_add(alpha_a).wrapping_add(alpha_b));
+    }
+    let xs_bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+
+    // One result per bit of each input
+    let mut results_a = vec![0i64; 32 * n_values];
+    let mut results_b = vec![0i64; 32 * n_values];
+    unsafe {
+        eval(
+            0,
+            xs_bytes.as_ptr(),
+            keys_a.as_ptr(),
+            results_a.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+        eval(
+            1,
+            xs_bytes.as_ptr(),
+            keys_b.as_ptr(),
+            results_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+    }
+
+    for i in 0..n_values {
+        for j in 0..32 {
+            let result = (results_a[32 * i + j] as u32).wrapping_add(results_b[32 * i + j] as u32);
+            assert_eq!(result, (plain_xs[i] >> j) & 1);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::eq::*;
+use sycret::le::*;
+use sycret::stream::{prg_header, FSSKey, Prg, PrgId, RawKey, PRG_HEADER_LEN};
+use sycret::utils::{ChaCha12Prg, ChaCha20Prg, ChaCha8Prg, Mmo};
+use sycret::{eval_with_prg, keygen_with_prg};
+
+#[test]
+fn expand_batch_matches_expand_array() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut mmo = Mmo::new(&aes_keys);
+    let mut chacha = ChaCha8Prg::new(&aes_keys);
+
+    // Batches with a partial chunk at the end.
+    let seeds: Vec<u128> = (0..21).map(|_| rng.gen()).collect();
+    let mut out = vec![[0u128; 3]; seeds.len()];
+    let mut out_2 = vec![[0u128; 2]; seeds.len()];
+    mmo.expand_batch(&seeds, &mut out);
+    mmo.expand_batch(&seeds, &mut out_2);
+    for ((seed, expanded), expanded_2) in seeds.iter().zip(out.iter()).zip(out_2.iter()) {
+        assert_eq!(mmo.expand_array::<3>(*seed), *expanded);
+        // Shorter arrays are prefixes of the expansion.
+        assert_eq!(expanded[0..2], *expanded_2);
+    }
+
+    chacha.expand_batch(&seeds, &mut out);
+    for (seed, expanded) in seeds.iter().zip(out.iter()) {
+        assert_eq!(chacha.expand_array::<3>(*seed), *expanded);
+    }
+}
+
+#[test]
+fn expand_and_from_slice_keep_their_contract() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let seed: u128 = rng.gen();
+
+    // `expand` gives all the blocks of `expand_array`.
+    let mut mmo = Mmo::new(&aes_keys);
+    let out: [u128; 4] = mmo.expand_array(seed);
+    assert_eq!(mmo.expand(seed), out);
+    let mut chacha = ChaCha8Prg::new(&[aes_keys[0], aes_keys[1], aes_keys[2]]);
+    assert_eq!(chacha.expand(seed), chacha.expand_array::<3>(seed));
+
+    // `from_slice` takes the first keys and pads with zero keys.
+    assert_eq!(Mmo::<2>::from_slice(&aes_keys).expand(seed), out[0..2]);
+    let mut padded = Mmo::<3>::from_slice(&aes_keys[0..2]);
+    let expected: [u128; 3] = Mmo::new(&[aes_keys[0], aes_keys[1], 0]).expand_array(seed);
+    assert_eq!(padded.expand_array(seed), expected);
+}
+
+#[test]
+fn mmo_evaluates_keys_of_a_longer_mmo() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 4] = rng.gen();
+    let mut mmo = Mmo::new(&aes_keys);
+    let mut mmo_3 = Mmo::<3>::from_slice(&aes_keys[0..3]);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = EqKey::generate_keypair(&mut mmo);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        let t_a_output = k_a.eval(&mut mmo_3, 0, alpha);
+        let t_b_output = k_b.eval(&mut mmo_3, 1, alpha);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), 1u32);
+
+        let (k_a, k_b) = LeKey::generate_keypair(&mut mmo);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        let x = alpha.wrapping_sub(1);
+        let t_a_output = k_a.eval(&mut mmo_3, 0, x);
+        let t_b_output = k_b.eval(&mut mmo_3, 1, x);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), (x <= alpha) as u32);
+    }
+}
+
+#[test]
+fn chacha_prg_expansion() {
+    let mut rng = rand::thread_rng();
+    let keys: [u128; 6] = rng.gen();
+    let mut prg = ChaCha20Prg::new(&keys);
+    assert_eq!(ChaCha20Prg::<6>::EXPANSION, 6);
+
+    // Deterministic, with the shorter arrays as prefixes of the keystream.
+    let seed: u128 = rng.gen();
+    let out: [u128; 6] = prg.expand_array(seed);
+    assert_eq!(out, prg.expand_array(seed));
+    assert_eq!(prg.expand_array::<3>(seed), out[0..3]);
+    assert_ne!(out, prg.expand_array(seed ^ 1));
+
+    // The rounds and each of the public keys change the output.
+    assert_ne!(ChaCha8Prg::new(&keys).expand_array(seed), out);
+    for i in 0..6 {
+        let mut other_keys = keys;
+        other_keys[i] ^= 1;
+        assert_ne!(ChaCha20Prg::new(&other_keys).expand_array(seed), out);
+    }
+}
+
+#[test]
+fn chacha_prg_generate_and_evaluate() {
+    let mut rng = rand::thread_rng();
+    let keys: [u128; 3] = rng.gen();
+    let mut prg = ChaCha12Prg::new(&keys);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = EqKey::generate_keypair(&mut prg);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        let t_a_output = k_a.eval(&mut prg, 0, alpha);
+        let t_b_output = k_b.eval(&mut prg, 1, alpha);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), 1u32);
+
+        let (k_a, k_b) = LeKey::generate_keypair(&mut prg);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        let x = alpha.wrapping_add(1);
+        let t_a_output = k_a.eval(&mut prg, 0, x);
+        let t_b_output = k_b.eval(&mut prg, 1, x);
+        assert_eq!(t_a_output.wrapping_add(t_b_output), (x <= alpha) as u32);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_with_prg_header() {
+    let op_id: usize = 1;
+    let n_values: usize = 50;
+    let line_len = PRG_HEADER_LEN + LeKey::KEY_LEN;
+
+    for &prg_id in &[PrgId::Mmo, PrgId::ChaCha8, PrgId::ChaCha12, PrgId::ChaCha20] {
+        let mut keys_a = vec![0u8; line_len * n_values];
+        let mut keys_b = vec![0u8; line_len * n_values];
+        unsafe {
+            keygen_with_prg(
+                keys_a.as_mut_ptr(),
+                keys_b.as_mut_ptr(),
+                n_values,
+                2,
+                op_id,
+                std::ptr::null(),
+                0,
+                std::ptr::null(),
+                0,
+                prg_id as usize,
+            );
+        }
+
+        // Each line records the Prg and the default keys, followed by the usual key.
+        let header = prg_header(prg_id, &[0, 1, 2]);
+        assert_eq!(header[0], prg_id as u8);
+        let mut xs: Vec<u8> = vec![];
+        for i in 0..n_values {
+            assert_eq!(keys_a[line_len * i..line_len * i + PRG_HEADER_LEN], header);
+            assert_eq!(keys_b[line_len * i..line_len * i + PRG_HEADER_LEN], header);
+            let (k_a, k_b) = unsafe {
+                (
+                    LeKey::from_raw_line(keys_a.as_ptr().add(line_len * i + PRG_HEADER_LEN)),
+                    LeKey::from_raw_line(keys_b.as_ptr().add(line_len * i + PRG_HEADER_LEN)),
+                )
+            };
+            let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+            xs.extend_from_slice(&alpha.to_le_bytes());
+        }
+
+        let mut results = vec![0i64; 2 * n_values];
+        for (party_id, keys) in [&keys_a, &keys_b].iter().enumerate() {
+            unsafe {
+                eval_with_prg(
+                    party_id,
+                    xs.as_ptr(),
+                    keys.as_ptr(),
+                    results.as_mut_ptr().add(party_id * n_values),
+                    n_values,
+                    2,
+                    op_id,
+                    std::ptr::null(),
+                    0,
+                    prg_id as usize,
+                );
+            }
+        }
+        for i in 0..n_values {
+            assert_eq!(
+                (results[i] as u32).wrapping_add(results[n_values + i] as u32),
+                1
+            );
+        }
+    }
+}
+
+#[test]
+fn eval_batch_matches_eval() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // Not a multiple of the AES pipeline width.
+    let n_keys = 13;
+    let (eq_a, _): (Vec<_>, Vec<_>) = (0..n_keys)
+        .map(|_| EqKey::generate_keypair(&mut prg))
+        .unzip();
+    let (le_a, _): (Vec<_>, Vec<_>) = (0..n_keys)
+        .map(|_| LeKey::generate_keypair(&mut prg))
+        .unzip();
+    let mut xs: Vec<u32> = (0..n_keys).map(|_| rng.gen()).collect();
+    xs[0] = eq_a[0].alpha_share;
+
+    for party_id in 0..2 {
+        let mut out = vec![0u32; n_keys];
+        EqKey::eval_batch(&eq_a, &mut prg, party_id, &xs, &mut out);
+        for ((key, &x), &out) in eq_a.iter().zip(xs.iter()).zip(out.iter()) {
+            assert_eq!(key.eval(&mut prg, party_id, x), out);
+        }
+
+        LeKey::eval_batch(&le_a, &mut prg, party_id, &xs, &mut out);
+        for ((key, &x), &out) in le_a.iter().zip(xs.iter()).zip(out.iter()) {
+            assert_eq!(key.eval(&mut prg, party_id, x), out);
+        }
+    }
+}
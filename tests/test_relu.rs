@@ -0,0 +1,111 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::relu::*;
+use sycret::stream::{FSSKey, RawKey};
+use sycret::utils::Mmo;
+use sycret::{eval, keygen};
+
+fn relu(x: u32) -> u32 {
+    if (x as i32) < 0 {
+        0
+    } else {
+        x
+    }
+}
+
+#[test]
+fn generate_and_evaluate_through_raw_lines() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(ReluKey::KEY_LEN, 1152);
+
+    for _ in 0..16 {
+        let (k_a, k_b) = ReluKey::generate_keypair(&mut prg);
+
+        // Serialize and deserialize the keys.
+        let mut line_a = vec![0u8; ReluKey::KEY_LEN];
+        let mut line_b = vec![0u8; ReluKey::KEY_LEN];
+        let (k_a, k_b) = unsafe {
+            k_a.to_raw_line(line_a.as_mut_ptr());
+            k_b.to_raw_line(line_b.as_mut_ptr());
+            (
+                ReluKey::from_raw_line(line_a.as_ptr()),
+                ReluKey::from_raw_line(line_b.as_ptr()),
+            )
+        };
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+
+        // Edge cases around 0 and the sign bit, then random values.
+        let mut xs: Vec<u32> = vec![0, 1, u32::MAX, 1 << 31, (1 << 31) - 1, (1 << 31) + 1];
+        // Masked inputs with the lower bits at 0, at alpha_low and at their largest value.
+        for &masked_x in &[0u32, alpha, alpha ^ (1 << 31), (1 << 31) - 1, u32::MAX] {
+            xs.push(masked_x.wrapping_sub(alpha));
+        }
+        xs.extend((0..32).map(|_| rng.gen::<u32>()));
+        for &x in xs.iter() {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output = k_a.eval(&mut prg, 0, masked_x);
+            let b_output = k_b.eval(&mut prg, 1, masked_x);
+            assert_eq!(a_output.wrapping_add(b_output), relu(x));
+        }
+    }
+}
+
+#[test]
+fn batch_generate_and_evaluate() {
+    // op_id = 7 implies ReLU keys
+    let op_id: usize = 7;
+    let n_values: usize = 16;
+
+    let mut keys_a = vec![0u8; ReluKey::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; ReluKey::KEY_LEN * n_values];
+    unsafe {
+        keygen(keys_a.as_mut_ptr(), keys_b.as_mut_ptr(), n_values, 2, op_id);
+    }
+
+    let mut rng = rand::thread_rng();
+    let plain_xs: Vec<u32> = (0..n_values).map(|_| rng.gen::<u32>()).collect();
+    let mut xs: Vec<u32> = vec![];
+    for (i, plain_x) in plain_xs.iter().enumerate() {
+        let (k_a, k_b) = unsafe {
+            (
+                ReluKey::from_raw_line(keys_a.as_ptr().add(ReluKey::KEY_LEN * i)),
+                ReluKey::from_raw_line(keys_b.as_ptr().add(ReluKey::KEY_LEN * i)),
+            )
+        };
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        xs.push(plain_x.wrapping_add(alpha));
+    }
+    let xs_bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+
+    let mut results_a = vec![0i64; n_values];
+    let mut results_b = vec![0i64; n_values];
+    unsafe {
+        eval(
+            0,
+            xs_bytes.as_ptr(),
+            keys_a.as_ptr(),
+            results_a.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+        eval(
+            1,
+            xs_bytes.as_ptr(),
+            keys_b.as_ptr(),
+            results_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+    }
+
+    for i in 0..n_values {
+        let result = (results_a[i] as u32).wrapping_add(results_b[i] as u32);
+        assert_eq!(result, relu(plain_xs[i]));
+    }
+}
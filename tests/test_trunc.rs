@@ -0,0 +1,224 @@
+use rand::Rng;
+
+extern crate sycret;
+use sycret::stream::{generate_key_stream, PrgId, RawKey};
+use sycret::trunc::*;
+use sycret::utils::{seeded_rng, Mmo};
+use sycret::{eval, keygen_with_params};
+
+#[test]
+fn generate_and_evaluate_through_raw_lines() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    assert_eq!(TruncKey::KEY_LEN, 1813);
+
+    for &f in [1u8, 8, 12, 31].iter() {
+        let (k_a, k_b) = TruncKey::generate_keypair(&mut prg, f);
+
+        // Serialize and deserialize the keys.
+        let mut line_a = vec![0u8; TruncKey::KEY_LEN];
+        let mut line_b = vec![0u8; TruncKey::KEY_LEN];
+        let (k_a, k_b) = unsafe {
+            k_a.to_raw_line(line_a.as_mut_ptr());
+            k_b.to_raw_line(line_b.as_mut_ptr());
+            (
+                TruncKey::from_raw_line(line_a.as_ptr()),
+                TruncKey::from_raw_line(line_b.as_ptr()),
+            )
+        };
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+
+        // Wrap-arounds of the masked input and of its lower bits.
+        let mut xs: Vec<u32> = vec![0, 1, u32::MAX, 0u32.wrapping_sub(alpha), (1 << f) - 1];
+        xs.extend((0..32).map(|_| rng.gen::<u32>()));
+        for &x in xs.iter() {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output = k_a.eval(&mut prg, 0, masked_x);
+            let b_output = k_b.eval(&mut prg, 1, masked_x);
+            assert_eq!(a_output.wrapping_add(b_output), x >> f);
+
+            let a_output = k_a.eval_arithmetic(&mut prg, 0, masked_x);
+            let b_output = k_b.eval_arithmetic(&mut prg, 1, masked_x);
+            assert_eq!(a_output.wrapping_add(b_output), ((x as i32) >> f) as u32);
+        }
+    }
+}
+
+#[test]
+fn generate_reproducible_keys_from_seed() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let seed: u128 = rng.gen();
+    let f = 12u8;
+
+    // The same seed gives the same raw lines, another seed does not.
+    let mut lines = vec![vec![0u8; TruncKey::KEY_LEN]; 6];
+    let keys = [
+        TruncKey::generate_keypair_from_rng(&mut prg, f, &mut seeded_rng(seed)),
+        TruncKey::generate_keypair_from_rng(&mut prg, f, &mut seeded_rng(seed)),
+        TruncKey::generate_keypair_from_rng(&mut prg, f, &mut seeded_rng(seed ^ 1)),
+    ];
+    for (i, (k_a, k_b)) in keys.iter().enumerate() {
+        unsafe {
+            k_a.to_raw_line(lines[2 * i].as_mut_ptr());
+            k_b.to_raw_line(lines[2 * i + 1].as_mut_ptr());
+        }
+    }
+    assert_eq!(lines[0..2], lines[2..4]);
+    assert_ne!(lines[0], lines[4]);
+
+    // Seeded keys are still valid keys.
+    let (k_a, k_b) = &keys[0];
+    let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+    let x: u32 = rng.gen();
+    let masked_x = x.wrapping_add(alpha);
+    let out = k_a
+        .eval(&mut prg, 0, masked_x)
+        .wrapping_add(k_b.eval(&mut prg, 1, masked_x));
+    assert_eq!(out, x >> f);
+}
+
+#[test]
+#[should_panic(expected = "op_id 12 expects 0 < f < 32, got 259")]
+fn generate_with_too_many_bits() {
+    // 259 would be a 3-bit truncation as a byte. Through the stream,
+    // since a panic cannot unwind out of the C ABI.
+    let mut keys_a = vec![0u8; TruncKey::KEY_LEN];
+    let mut keys_b = vec![0u8; TruncKey::KEY_LEN];
+    generate_key_stream(
+        &[0, 1, 2],
+        0,
+        1,
+        keys_a.as_mut_ptr() as usize,
+        keys_b.as_mut_ptr() as usize,
+        12,
+        &[259],
+        PrgId::Mmo,
+        false,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Truncation key with 0 < f < 32 expected, got 40")]
+fn read_corrupt_line() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+    let (k_a, _) = TruncKey::generate_keypair(&mut prg, 8);
+    let mut line = vec![0u8; TruncKey::KEY_LEN];
+    unsafe {
+        k_a.to_raw_line(line.as_mut_ptr());
+        // The byte of f, after the shares of alpha and of its higher bits.
+        line[8] = 40;
+        TruncKey::from_raw_line(line.as_ptr());
+    }
+}
+
+#[test]
+fn batch_generate_and_evaluate_fixed_point() {
+    // op_id = 13 implies truncation keys with an arithmetic shift
+    let op_id: usize = 13;
+    let n_values: usize = 16;
+    let params: [u64; 1] = [16];
+
+    let mut keys_a = vec![0u8; TruncKey::KEY_LEN * n_values];
+    let mut keys_b = vec![0u8; TruncKey::KEY_LEN * n_values];
+    unsafe {
+        keygen_with_params(
+            keys_a.as_mut_ptr(),
+            keys_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+            params.as_ptr(),
+            params.len(),
+        );
+    }
+
+    // Products of fixed-point numbers with 16 bits of precision, positive and negative.
+    let mut rng = rand::thread_rng();
+    let plain_xs: Vec<i32> = (0..n_values)
+        .map(|_| rng.gen_range(-1000, 1000) * rng.gen_range(-1000, 1000))
+        .collect();
+    let mut xs: Vec<u32> = vec![];
+    for (i, plain_x) in plain_xs.iter().enumerate() {
+        let (k_a, k_b) = unsafe {
+            (
+                TruncKey::from_raw_line(keys_a.as_ptr().add(TruncKey::KEY_LEN * i)),
+                TruncKey::from_raw_line(keys_b.as_ptr().add(TruncKey::KEY_LEN * i)),
+            )
+        };
+        assert_eq!(k_a.f, 16);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        xs.push((*plain_x as u32).wrapping_add(alpha));
+    }
+    let xs_bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+
+    let mut results_a = vec![0i64; n_values];
+    let mut results_b = vec![0i64; n_values];
+    unsafe {
+        eval(
+            0,
+            xs_bytes.as_ptr(),
+            keys_a.as_ptr(),
+            results_a.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+        eval(
+            1,
+            xs_bytes.as_ptr(),
+            keys_b.as_ptr(),
+            results_b.as_mut_ptr(),
+            n_values,
+            2,
+            op_id,
+        );
+    }
+
+    for i in 0..n_values {
+        let result = (results_a[i] as u32).wrapping_add(results_b[i] as u32);
+        assert_eq!(result as i32, plain_xs[i] >> 16);
+    }
+}
+
+#[test]
+fn generate_and_evaluate_other_domains() {
+    let mut rng = rand::thread_rng();
+    let aes_keys: [u128; 3] = rng.gen();
+    let mut prg = Mmo::new(&aes_keys);
+
+    // The whole 8 bits domain is small enough to be checked exhaustively.
+    for f in 1..8u8 {
+        let (k_a, k_b) = GenericTruncKey::<u8, u64>::generate_keypair(&mut prg, f);
+        // The borrow tree only has one level per truncated bit.
+        assert_eq!(k_a.cw_low.len(), f as usize);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for x in 0..=255u8 {
+            let masked_x = x.wrapping_add(alpha);
+            let a_output = k_a.eval(&mut prg, 0, masked_x);
+            let b_output = k_b.eval(&mut prg, 1, masked_x);
+            assert_eq!(a_output.wrapping_add(b_output), (x >> f) as u64);
+        }
+    }
+
+    for &f in [1u8, 16, 40, 63].iter() {
+        let (k_a, k_b) = GenericTruncKey::<u64, u64>::generate_keypair(&mut prg, f);
+        let alpha = k_a.alpha_share.wrapping_add(k_b.alpha_share);
+        for _ in 0..32 {
+            let x: u64 = rng.gen();
+            let masked_x = x.wrapping_add(alpha);
+            let a_output = k_a.eval(&mut prg, 0, masked_x);
+            let b_output = k_b.eval(&mut prg, 1, masked_x);
+            assert_eq!(a_output.wrapping_add(b_output), x >> f);
+
+            let a_output = k_a.eval_arithmetic(&mut prg, 0, masked_x);
+            let b_output = k_b.eval_arithmetic(&mut prg, 1, masked_x);
+            assert_eq!(a_output.wrapping_add(b_output), ((x as i64) >> f) as u64);
+        }
+    }
+}